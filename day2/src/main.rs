@@ -1,171 +1,115 @@
 // https://adventofcode.com/2019/day/2
 
-use std::fs;
+use std::collections::VecDeque;
+
+use intcode::computer::ProgramState;
+use intcode::error::IntcodeError;
+use intcode::loader::{self, Source};
+use intcode::search::Search;
+use intcode::symbolic::SymbolicMachine;
 
 // Once you have a working computer, the first step is to restore the gravity assist program (your puzzle input)
 // to the "1202 program alarm" state it had just before the last computer caught fire.
 // To do this, before running the program, replace position 1 with the value 12 and replace position 2
 // with the value 2. What value is left at position 0 after the program halts?
 fn main() {
-    let input = fs::read_to_string("input.txt").expect("input program read");
-
-    part1(input.clone());
-    part2(input.clone());
+    // the puzzle input is built in; a program file, or - for stdin, given as argument replaces it
+    let arg = std::env::args().nth(1);
+    let source = match &arg {
+        Some(arg) => Source::from_arg(arg),
+        None => Source::Embedded(include_bytes!("../input.txt")),
+    };
+    let numbers = loader::load(source).unwrap_or_else(|e| {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    });
+
+    part1(&numbers);
+    part2(&numbers);
+    part2_symbolic(&numbers);
 }
 
-fn part1(input: String) {
-    let mut numbers: Vec<i64> = str::split(&input, ",")
-        .map(|n| n.parse::<i64>().expect("number parsed"))
-        .collect();
+fn part1(program: &[i64]) {
+    let mut numbers = program.to_vec();
 
     numbers[1] = 12;
     numbers[2] = 2;
 
     println!("original program: {:?}", numbers);
 
-    run_program(&mut numbers);
+    run_program(&mut numbers).expect("program halted");
 
     println!("completed program: {:?}", numbers);
     println!("first element: {:?}", numbers[0])
 }
 
-struct ProgramState {
-    memory: Vec<i64>,
-    ip: usize,
+fn part2(numbers: &[i64]) {
+    // noun at address 1, verb at address 2; patched programs that fault or never halt are skipped
+    let found = Search::new(numbers)
+        .patch(1, 0..100)
+        .patch(2, 0..100)
+        .step_limit(STEP_LIMIT)
+        .first(|outcome| outcome.peek(0) == TARGET)
+        .expect("100 * 100 candidates");
+
+    match found.as_deref() {
+        Some(&[noun, verb]) => {
+            println!("noun: {}, verb: {}", noun, verb);
+            println!("100 * noun + verb: {}", 100 * noun + verb);
+        }
+        _ => println!("no noun and verb produce {}", TARGET),
+    }
 }
 
-struct Instruction {
-    opcode: InstructionType,
-    params: Vec<i64>,
+// the same answer without running candidates: [0] comes out as a formula in noun and verb
+fn part2_symbolic(numbers: &[i64]) {
+    let mut machine = SymbolicMachine::new(numbers);
+    machine.bind(1, "noun");
+    machine.bind(2, "verb");
+    machine.run().expect("program halted");
+
+    let result = machine.peek(0).expect("[0] known");
+    println!("[0] = {}", result);
+
+    let solutions = result
+        .solve(TARGET, &[("noun", 0..100), ("verb", 0..100)])
+        .expect("linear in verb");
+    for solution in solutions {
+        println!("noun: {}, verb: {}", solution[0], solution[1]);
+    }
 }
 
-enum InstructionType {
-    ADD,
-    MULTIPLY,
-    HALT,
-}
+const TARGET: i64 = 19690720;
 
-impl ProgramState {
-    fn next_instruction(&mut self) -> Option<Instruction> {
-        let opcode = self.memory[self.ip];
-        match opcode {
-            1 => {
-                let a = self.memory[self.ip + 1];
-                let b = self.memory[self.ip + 2];
-                let c = self.memory[self.ip + 3];
-
-                self.ip += 4;
-                Some(Instruction {
-                    opcode: InstructionType::ADD,
-                    params: vec![a, b, c],
-                })
-            }
-            2 => {
-                let a = self.memory[self.ip + 1];
-                let b = self.memory[self.ip + 2];
-                let c = self.memory[self.ip + 3];
-
-                self.ip += 4;
-                Some(Instruction {
-                    opcode: InstructionType::MULTIPLY,
-                    params: vec![a, b, c],
-                })
-            }
-            99 => {
-                self.ip += 1;
-                Some(Instruction {
-                    opcode: InstructionType::HALT,
-                    params: vec![],
-                })
-            }
-            _ => None,
-        }
-    }
-}
+// far more instructions than the gravity assist program executes for any noun and verb
+const STEP_LIMIT: u64 = 10_000;
 
-fn part2(input: String) {
-    let numbers: Vec<i64> = str::split(&input, ",")
-        .map(|n| n.parse::<i64>().expect("number parsed"))
-        .collect();
-    let mut state = ProgramState {
-        memory: numbers.clone(),
-        ip: 0,
-    };
+fn run_program(numbers: &mut Vec<i64>) -> Result<(), IntcodeError> {
+    let mut state = ProgramState::new(numbers, VecDeque::new(), vec![]);
+    state.set_step_limit(Some(STEP_LIMIT));
 
-    const TARGET: usize = 19690720;
-
-    for noun in 0..100 {
-        for verb in 0..100 {
-            state.memory = numbers.clone();
-            state.memory[1] = noun;
-            state.memory[2] = verb;
-            state.ip = 0;
-
-            loop {
-                match state.next_instruction() {
-                    Some(instruction) => match instruction.opcode {
-                        InstructionType::ADD => {
-                            let a = instruction.params[0] as usize;
-                            let b = instruction.params[1] as usize;
-                            let c: usize = instruction.params[2] as usize;
-
-                            state.memory[c] = state.memory[a] + state.memory[b];
-                        }
-                        InstructionType::MULTIPLY => {
-                            let a = instruction.params[0] as usize;
-                            let b = instruction.params[1] as usize;
-                            let c = instruction.params[2] as usize;
-
-                            state.memory[c] = state.memory[a] * state.memory[b];
-                        }
-                        InstructionType::HALT => {
-                            break;
-                        }
-                    },
-                    None => {
-                        panic!("invalid opcode");
-                    }
-                }
-            }
-
-            if state.memory[0] == TARGET as i64 {
-                println!("noun: {}, verb: {}", noun, verb);
-                println!("100 * noun + verb: {}", 100 * noun + verb);
-                break;
-            }
-        }
-    }
+    state.run_to_halt()?;
+    *numbers = state.memory();
+
+    Ok(())
 }
 
-fn run_program(numbers: &mut Vec<i64>) {
-    let mut i = 0;
-
-    loop {
-        match numbers[i] {
-            1 => {
-                let a = numbers[i + 1] as usize;
-                let b = numbers[i + 2] as usize;
-                let c = numbers[i + 3] as usize;
-
-                numbers[c] = numbers[a] + numbers[b];
-
-                i += 4;
-            }
-            2 => {
-                let a = numbers[i + 1] as usize;
-                let b = numbers[i + 2] as usize;
-                let c = numbers[i + 3] as usize;
-
-                numbers[c] = numbers[a] * numbers[b];
-
-                i += 4;
-            }
-            99 => {
-                break;
-            }
-            _ => {
-                panic!("invalid opcode");
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use crate::run_program;
+
+    #[test]
+    fn test_run_program() {
+        let mut numbers = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        run_program(&mut numbers).unwrap();
+        assert_eq!(numbers, vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        let mut numbers = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
+        run_program(&mut numbers).unwrap();
+        assert_eq!(numbers, vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+
+        // jumps back to itself forever
+        let mut numbers = vec![1105, 1, 0];
+        assert!(run_program(&mut numbers).is_err());
     }
 }
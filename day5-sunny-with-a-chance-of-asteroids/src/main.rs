@@ -1,58 +1,116 @@
-mod computer;
+use std::collections::VecDeque;
 
-use computer::ProgramState;
+use intcode::cfg::Cfg;
+use intcode::computer::ProgramState;
+use intcode::disassembler;
+use intcode::loader::{self, Source};
+use intcode::selfmod::SelfModTracer;
+use intcode::trace::{JsonLinesTracer, TextTracer};
 
 fn main() {
-    let input = String::from( "3,225,1,225,6,6,1100,1,238,225,104,0,1102,27,28,225,1,113,14,224,1001,224,-34,224,4,224,102,8,223,223,101,7,224,224,1,224,223,223,1102,52,34,224,101,-1768,224,224,4,224,1002,223,8,223,101,6,224,224,1,223,224,223,1002,187,14,224,1001,224,-126,224,4,224,102,8,223,223,101,2,224,224,1,224,223,223,1102,54,74,225,1101,75,66,225,101,20,161,224,101,-54,224,224,4,224,1002,223,8,223,1001,224,7,224,1,224,223,223,1101,6,30,225,2,88,84,224,101,-4884,224,224,4,224,1002,223,8,223,101,2,224,224,1,224,223,223,1001,214,55,224,1001,224,-89,224,4,224,102,8,223,223,1001,224,4,224,1,224,223,223,1101,34,69,225,1101,45,67,224,101,-112,224,224,4,224,102,8,223,223,1001,224,2,224,1,223,224,223,1102,9,81,225,102,81,218,224,101,-7290,224,224,4,224,1002,223,8,223,101,5,224,224,1,223,224,223,1101,84,34,225,1102,94,90,225,4,223,99,0,0,0,677,0,0,0,0,0,0,0,0,0,0,0,1105,0,99999,1105,227,247,1105,1,99999,1005,227,99999,1005,0,256,1105,1,99999,1106,227,99999,1106,0,265,1105,1,99999,1006,0,99999,1006,227,274,1105,1,99999,1105,1,280,1105,1,99999,1,225,225,225,1101,294,0,0,105,1,0,1105,1,99999,1106,0,300,1105,1,99999,1,225,225,225,1101,314,0,0,106,0,0,1105,1,99999,1007,677,677,224,102,2,223,223,1005,224,329,101,1,223,223,1108,226,677,224,1002,223,2,223,1005,224,344,101,1,223,223,1008,677,677,224,102,2,223,223,1005,224,359,101,1,223,223,8,226,677,224,1002,223,2,223,1006,224,374,101,1,223,223,108,226,677,224,1002,223,2,223,1006,224,389,1001,223,1,223,1107,226,677,224,102,2,223,223,1005,224,404,1001,223,1,223,7,226,677,224,1002,223,2,223,1005,224,419,101,1,223,223,1107,677,226,224,102,2,223,223,1006,224,434,1001,223,1,223,1107,226,226,224,1002,223,2,223,1006,224,449,101,1,223,223,1108,226,226,224,1002,223,2,223,1005,224,464,101,1,223,223,8,677,226,224,102,2,223,223,1005,224,479,101,1,223,223,8,226,226,224,1002,223,2,223,1006,224,494,1001,223,1,223,1007,226,677,224,1002,223,2,223,1006,224,509,1001,223,1,223,108,226,226,224,1002,223,2,223,1006,224,524,1001,223,1,223,1108,677,226,224,102,2,223,223,1006,224,539,101,1,223,223,1008,677,226,224,102,2,223,223,1006,224,554,101,1,223,223,107,226,677,224,1002,223,2,223,1006,224,569,101,1,223,223,107,677,677,224,102,2,223,223,1006,224,584,101,1,223,223,7,677,226,224,102,2,223,223,1005,224,599,101,1,223,223,1008,226,226,224,1002,223,2,223,1005,224,614,1001,223,1,223,107,226,226,224,1002,223,2,223,1005,224,629,101,1,223,223,7,226,226,224,102,2,223,223,1006,224,644,1001,223,1,223,1007,226,226,224,102,2,223,223,1006,224,659,101,1,223,223,108,677,677,224,102,2,223,223,1005,224,674,1001,223,1,223,4,223,99,226");
+    let numbers = loader::load(Source::Embedded(include_bytes!("../input.txt")))
+        .expect("diagnostic program loaded");
 
-    let result = part2(input);
+    if std::env::args().any(|arg| arg == "--disassemble") {
+        print!("{}", disassembler::listing(&numbers));
+        return;
+    }
+
+    // control-flow graph of the program as Graphviz DOT; the prologue patches the opcode at 6
+    // before running it, so the graph shows the code as the system ID 5 diagnostic ran it
+    if std::env::args().any(|arg| arg == "--cfg") {
+        let mut self_modifications = SelfModTracer::default();
+        let mut state = ProgramState::new(&numbers, VecDeque::from(vec![5]), vec![]);
+        state.set_tracer(&mut self_modifications);
+        state.run_to_halt().expect("diagnostic halted");
+        drop(state);
+
+        print!(
+            "{}",
+            Cfg::build(&self_modifications.patched(&numbers)).to_dot()
+        );
+        return;
+    }
 
+    let result = part1(&numbers);
     println!("result: {:?}", result);
+
+    let result = part2(&numbers);
+    println!("result: {:?}", result);
+}
+
+// air conditioner unit, system ID 1
+fn part1(numbers: &[i64]) -> Result<(), anyhow::Error> {
+    run_diagnostic(numbers, 1)
 }
 
-fn part2(input: String) -> Result<(), anyhow::Error> {
-    let mut numbers: Vec<i64> = str::split(&input, ",")
-        .map(|n| n.parse::<i64>().expect("number parsed"))
-        .collect();
+// thermal radiator controller, system ID 5
+fn part2(numbers: &[i64]) -> Result<(), anyhow::Error> {
+    run_diagnostic(numbers, 5)
+}
 
-    let input = vec![1];
-    let mut output = vec![0; 100];
-    let mut state = ProgramState::new(&mut numbers, input, &mut output);
+fn run_diagnostic(numbers: &[i64], system_id: i64) -> Result<(), anyhow::Error> {
+    let input = VecDeque::from(vec![system_id]);
+    let mut output = vec![];
+    let mut self_modifications = SelfModTracer::default();
+    let mut state = ProgramState::new(numbers, input, &mut output);
 
-    match state.run() {
-        Ok(_) => {}
-        Err(e) => panic!("error running program: {:?}", e),
+    // --trace and --trace-json write every executed instruction to stderr,
+    // --self-modifying lists the writes to executed cells,
+    // --profile adds hot spots, unexecuted code and an annotated listing of the code as it ran
+    let profiling = std::env::args().any(|arg| arg == "--profile");
+    if std::env::args().any(|arg| arg == "--trace") {
+        state.set_tracer(TextTracer(std::io::stderr()));
+    } else if std::env::args().any(|arg| arg == "--trace-json") {
+        state.set_tracer(JsonLinesTracer(std::io::stderr()));
+    } else if profiling || std::env::args().any(|arg| arg == "--self-modifying") {
+        state.set_tracer(&mut self_modifications);
     }
+    state.set_profiling(profiling);
+
+    state.run_to_halt()?;
 
     println!("final state: {:?}", state);
+    let profile = state.profile().cloned();
+    drop(state);
+
+    println!("output: {:?}", output);
+    for modification in self_modifications.report() {
+        println!("self-modifying: {}", modification);
+    }
+    if let Some(profile) = profile {
+        let code = self_modifications.patched(numbers);
+        print!("{}", profile.report(&code, 10));
+        print!("{}", profile.annotated(&code));
+    }
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
     use crate::ProgramState;
 
     #[test]
     fn test_save_print() {
-        let mut prog = vec![3, 0, 4, 0, 99];
-        let input = vec![42];
-        let mut output = vec![0];
-        let mut state = ProgramState::new(&mut prog, input, &mut output);
+        let prog = vec![3, 0, 4, 0, 99];
+        let input = VecDeque::from(vec![42]);
+        let mut output = vec![];
+        let mut state = ProgramState::new(&prog, input, &mut output);
 
         loop {
             match state.next_instruction() {
                 Ok(instruction) => match instruction.run(&mut state) {
-                    Ok(cont) => {
-                        if !cont {
-                            break;
-                        }
-                    }
+                    Ok(Some(_)) => break,
+                    Ok(None) => {}
                     Err(e) => panic!("error running instruction: {:?}", e),
                 },
                 Err(e) => panic!("error getting next instruction: {:?}", e),
             }
         }
+        drop(state);
 
         assert_eq!(output, vec![42]);
     }
@@ -0,0 +1,84 @@
+// Reference interpreter against the fast engine on the puzzle inputs:
+//     cargo bench -p intcode --bench engines
+
+use std::collections::VecDeque;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use intcode::computer::{Engine, ProgramState};
+use intcode::loader;
+
+const DAY2: &str = include_str!("../../day2/input.txt");
+const DAY5: &str = include_str!("../../day5-sunny-with-a-chance-of-asteroids/input.txt");
+
+const ENGINES: [Engine; 2] = [Engine::Reference, Engine::Fast];
+
+fn parse(input: &str) -> Vec<i64> {
+    loader::parse_text(input).expect("puzzle input parsed")
+}
+
+// thermal radiator controller diagnostic, system ID 5
+fn day5(c: &mut Criterion) {
+    let program = parse(DAY5);
+    let mut group = c.benchmark_group("day5");
+
+    for engine in ENGINES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{engine:?}")),
+            &engine,
+            |b, &engine| {
+                b.iter(|| {
+                    let mut output = vec![];
+                    let mut state =
+                        ProgramState::new(&program, VecDeque::from(vec![5]), &mut output);
+                    state.set_engine(engine);
+                    state.run_to_halt().expect("program halted");
+                    drop(state);
+                    output
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// part 2 by brute force: every noun and verb from one snapshot until 19690720 turns up
+fn day2(c: &mut Criterion) {
+    let program = parse(DAY2);
+    let mut group = c.benchmark_group("day2");
+
+    for engine in ENGINES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{engine:?}")),
+            &engine,
+            |b, &engine| {
+                b.iter(|| {
+                    let mut state = ProgramState::new(&program, VecDeque::new(), vec![]);
+                    state.set_engine(engine);
+                    let start = state.snapshot();
+
+                    for noun in 0..100 {
+                        for verb in 0..100 {
+                            state.restore(&start);
+                            state.poke(1, noun);
+                            state.poke(2, verb);
+                            state.run_to_halt().expect("program halted");
+
+                            if state.peek(0) == 19690720 {
+                                return 100 * noun + verb;
+                            }
+                        }
+                    }
+
+                    unreachable!("no noun and verb produce 19690720")
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, day5, day2);
+criterion_main!(benches);
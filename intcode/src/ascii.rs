@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+use crate::computer::ProgramState;
+use crate::io::{Input, Output};
+
+// values below this are characters, anything else is a number like a final answer
+const ASCII_LIMIT: i64 = 128;
+
+// the codes of `line` as READ expects them, terminated by a newline
+pub fn encode(line: &str) -> Vec<i64> {
+    let mut codes: Vec<i64> = line.bytes().map(i64::from).collect();
+    if !line.ends_with('\n') {
+        codes.push(i64::from(b'\n'));
+    }
+    codes
+}
+
+// Output of an ASCII program split into its text and the values that are not characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rendered {
+    pub text: String,
+    pub values: Vec<i64>,
+}
+
+pub fn render(output: &[i64]) -> Rendered {
+    let mut rendered = Rendered::default();
+
+    for &value in output {
+        match value {
+            0..ASCII_LIMIT => rendered.text.push(value as u8 as char),
+            _ => rendered.values.push(value),
+        }
+    }
+
+    rendered
+}
+
+// Feeds lines of text to READ, one character per value, reading the next line from `reader`
+// once the previous one is used up; runs out at the end of the reader.
+pub struct AsciiInput<R> {
+    reader: R,
+    pending: VecDeque<i64>,
+}
+
+impl<R: BufRead> AsciiInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Input for AsciiInput<R> {
+    fn read(&mut self) -> Option<i64> {
+        if self.pending.is_empty() {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => self.pending.extend(encode(&line)),
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+// Writes characters to `writer` as they arrive, flushing at every newline, and keeps the values
+// that are not characters.
+pub struct AsciiOutput<W> {
+    writer: W,
+    pub values: Vec<i64>,
+}
+
+impl<W: Write> AsciiOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            values: vec![],
+        }
+    }
+}
+
+impl<W: Write> Output for AsciiOutput<W> {
+    fn write(&mut self, value: i64) -> Result<()> {
+        match value {
+            0..ASCII_LIMIT => {
+                self.writer.write_all(&[value as u8])?;
+                if value == i64::from(b'\n') {
+                    self.writer.flush()?;
+                }
+            }
+            _ => self.values.push(value),
+        }
+        Ok(())
+    }
+}
+
+// Runs an ASCII program with its input typed line by line on `reader` and its text written to
+// `writer`, e.g. stdin and stdout; returns the values that were not characters.
+pub fn interactive(program: &[i64], reader: impl BufRead, writer: impl Write) -> Result<Vec<i64>> {
+    let mut output = AsciiOutput::new(writer);
+    let mut state = ProgramState::new(program, AsciiInput::new(reader), &mut output);
+
+    state.run_to_halt()?;
+    drop(state);
+    output.writer.flush()?;
+
+    Ok(output.values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ascii::{encode, interactive, render, Rendered};
+
+    // prints "?\n", reads a character and the newline after it, then outputs the character, its
+    // code times 1000 and a newline
+    fn echo() -> Vec<i64> {
+        vec![
+            104, 63, 104, 10, 3, 100, 3, 101, 4, 100, 1002, 100, 1000, 100, 4, 100, 104, 10, 99,
+        ]
+    }
+
+    #[test]
+    fn encode_lines() {
+        assert_eq!(encode("NOT A J"), vec![78, 79, 84, 32, 65, 32, 74, 10]);
+        assert_eq!(encode("WALK\n"), vec![87, 65, 76, 75, 10]);
+        assert_eq!(encode(""), vec![10]);
+    }
+
+    #[test]
+    fn render_output() {
+        assert_eq!(
+            render(&[72, 105, 10, 19690720]),
+            Rendered {
+                text: "Hi\n".to_string(),
+                values: vec![19690720],
+            }
+        );
+    }
+
+    #[test]
+    fn interactive_session() {
+        let mut screen = vec![];
+        let values = interactive(&echo(), "x\n".as_bytes(), &mut screen).unwrap();
+
+        assert_eq!(String::from_utf8(screen).unwrap(), "?\nx\n");
+        assert_eq!(values, vec![120_000]);
+
+        // the program wants a line that never comes
+        assert!(interactive(&echo(), "".as_bytes(), vec![]).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+// Text assembly for the Intcode machine, the inverse of the disassembler:
+//
+//         .const LIMIT = 10      ; named constant
+//     loop:                      ; label, usable wherever a number is
+//         ADD [count], #1, [count]
+//         LT [count], #LIMIT, rb+0
+//         JT rb+0, #loop
+//         OUT [count]
+//         HLT
+//     count:
+//         .data 0                ; raw cells
+//
+// Operands are [x] for position, #x for immediate and rb+x / rb-x for relative mode, where x is
+// a number, a label or constant, optionally followed by +n or -n.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::computer::parse_instruction;
+use crate::disassembler::mnemonic;
+
+const OPCODES: [i64; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+enum Statement<'a> {
+    Instruction {
+        opcode: i64,
+        write_arg: Option<usize>,
+        operands: Vec<&'a str>,
+    },
+    Data(Vec<&'a str>),
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction { operands, .. } => 1 + operands.len(),
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+// Assembles `source` into a memory image loadable by `ProgramState::new`.
+pub fn assemble(source: &str) -> Result<Vec<i64>> {
+    let mut symbols: HashMap<&str, i64> = HashMap::new();
+    let mut statements = vec![];
+    let mut address = 0;
+
+    // first pass: parse statements and assign addresses to labels
+    for (index, line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_symbol(label) {
+                return Err(anyhow!("line {}: invalid label {:?}", line_no, label));
+            }
+            define(&mut symbols, label, address as i64, line_no)?;
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (head, rest) = match line.split_once(char::is_whitespace) {
+            Some((head, rest)) => (head, rest.trim()),
+            None => (line, ""),
+        };
+        let args: Vec<&str> = if rest.is_empty() {
+            vec![]
+        } else {
+            rest.split(',').map(|arg| arg.trim()).collect()
+        };
+
+        let statement = match head.to_ascii_lowercase().as_str() {
+            ".const" => {
+                let (name, value) = rest
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("line {}: expected .const NAME = value", line_no))?;
+                let name = name.trim();
+                if !is_symbol(name) {
+                    return Err(anyhow!(
+                        "line {}: invalid constant name {:?}",
+                        line_no,
+                        name
+                    ));
+                }
+                let value = evaluate(value.trim(), &symbols, line_no)?;
+                define(&mut symbols, name, value, line_no)?;
+                continue;
+            }
+            ".data" => Statement::Data(args),
+            _ => instruction(head, args, line_no)?,
+        };
+
+        address += statement.size();
+        statements.push((line_no, statement));
+    }
+
+    // second pass: encode with every label known
+    let mut memory = Vec::with_capacity(address);
+
+    for (line_no, statement) in statements {
+        match statement {
+            Statement::Data(values) => {
+                for value in values {
+                    memory.push(evaluate(value, &symbols, line_no)?);
+                }
+            }
+            Statement::Instruction {
+                opcode,
+                write_arg,
+                operands,
+            } => {
+                let mut code = opcode;
+                let mut params = vec![];
+
+                for (n, operand) in operands.iter().enumerate() {
+                    let (mode, param) = parse_operand(operand, &symbols, line_no)?;
+                    if mode == 1 && write_arg == Some(n) {
+                        return Err(anyhow!(
+                            "line {}: operand {:?} is written to and cannot be immediate",
+                            line_no,
+                            operand
+                        ));
+                    }
+
+                    code += mode * 10_i64.pow(n as u32 + 2);
+                    params.push(param);
+                }
+
+                memory.push(code);
+                memory.extend(params);
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+fn instruction<'a>(name: &str, operands: Vec<&'a str>, line_no: usize) -> Result<Statement<'a>> {
+    for opcode in OPCODES {
+        let decoded = parse_instruction(0, opcode)?;
+
+        if mnemonic(&decoded.opcode).eq_ignore_ascii_case(name) {
+            let count = decoded.opcode.arg_count();
+            if operands.len() != count {
+                return Err(anyhow!(
+                    "line {}: {} takes {} operands, got {}",
+                    line_no,
+                    name,
+                    count,
+                    operands.len()
+                ));
+            }
+
+            return Ok(Statement::Instruction {
+                opcode,
+                write_arg: decoded.opcode.write_arg(),
+                operands,
+            });
+        }
+    }
+
+    Err(anyhow!("line {}: unknown instruction {:?}", line_no, name))
+}
+
+// mode and parameter of [x], #x or rb+x
+fn parse_operand(
+    operand: &str,
+    symbols: &HashMap<&str, i64>,
+    line_no: usize,
+) -> Result<(i64, i64)> {
+    if let Some(inner) = operand.strip_prefix('[').and_then(|o| o.strip_suffix(']')) {
+        return Ok((0, evaluate(inner.trim(), symbols, line_no)?));
+    }
+    if let Some(value) = operand.strip_prefix('#') {
+        return Ok((1, evaluate(value.trim(), symbols, line_no)?));
+    }
+    if let Some(offset) = operand.strip_prefix("rb") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            return Ok((2, 0));
+        }
+        if let Some(value) = offset.strip_prefix('+') {
+            return Ok((2, evaluate(value.trim(), symbols, line_no)?));
+        }
+        if offset.starts_with('-') {
+            return Ok((2, evaluate(offset, symbols, line_no)?));
+        }
+    }
+
+    Err(anyhow!(
+        "line {}: invalid operand {:?}, expected [x], #x or rb+x",
+        line_no,
+        operand
+    ))
+}
+
+// number, or symbol with an optional leading - and an optional +n / -n offset
+fn evaluate(expr: &str, symbols: &HashMap<&str, i64>, line_no: usize) -> Result<i64> {
+    if let Ok(value) = expr.parse::<i64>() {
+        return Ok(value);
+    }
+
+    let (negated, body) = match expr.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, expr),
+    };
+    let (name, offset) = match body.find(['+', '-']) {
+        Some(pos) => {
+            let offset = body[pos..].replace(' ', "");
+            let offset = offset
+                .trim_start_matches('+')
+                .parse::<i64>()
+                .map_err(|_| anyhow!("line {}: invalid offset in {:?}", line_no, expr))?;
+            (body[..pos].trim(), offset)
+        }
+        None => (body, 0),
+    };
+
+    let value = match symbols.get(name) {
+        Some(&value) if negated => value.checked_neg(),
+        Some(&value) => Some(value),
+        None if is_symbol(name) => {
+            return Err(anyhow!("line {}: undefined symbol {:?}", line_no, name))
+        }
+        None => return Err(anyhow!("line {}: invalid value {:?}", line_no, expr)),
+    };
+    value
+        .and_then(|value| value.checked_add(offset))
+        .ok_or_else(|| anyhow!("line {}: {:?} overflows", line_no, expr))
+}
+
+fn define<'a>(
+    symbols: &mut HashMap<&'a str, i64>,
+    name: &'a str,
+    value: i64,
+    line_no: usize,
+) -> Result<()> {
+    if symbols.insert(name, value).is_some() {
+        return Err(anyhow!("line {}: {:?} is already defined", line_no, name));
+    }
+    Ok(())
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    name != "rb" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::assembler::assemble;
+    use crate::computer::{ProgramState, Status};
+    use crate::disassembler::listing;
+
+    #[test]
+    fn encode_modes() {
+        assert_eq!(
+            assemble("MUL [4], #3, [4]\nADD #7, #-2, rb+1\nOUT rb-3\nhlt").unwrap(),
+            vec![1002, 4, 3, 4, 21101, 7, -2, 1, 204, -3, 99]
+        );
+    }
+
+    #[test]
+    fn disassembler_round_trip() {
+        let memory = vec![
+            3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8, 109, 5, 1105, 1, 0, 2106, 0, 21107, 1, 2, -3,
+        ];
+        let source: String = listing(&memory)
+            .lines()
+            .map(|line| {
+                line.split_once(": ")
+                    .unwrap()
+                    .1
+                    .replacen("DATA", ".data", 1)
+                    + "\n"
+            })
+            .collect();
+
+        assert_eq!(assemble(&source).unwrap(), memory);
+    }
+
+    #[test]
+    fn labels_and_constants() {
+        let source = "
+                .const LIMIT = 3
+                .const ONE = 1
+            loop:                       ; count up to LIMIT
+                ADD [count], #1, [count]
+                OUT [count]
+                LT [count], #LIMIT, [flag]
+                JT [flag], #loop
+                ARB #end
+                OUT rb-ONE
+                OUT rb-LIMIT+2
+                HLT
+            count: .data 0
+            flag:  .data 0, end - 1
+            end:
+        ";
+        let memory = assemble(source).unwrap();
+        assert_eq!(memory.len(), 23);
+        assert_eq!(memory[22], 22);
+
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), &mut output);
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        drop(prog);
+
+        assert_eq!(output, vec![1, 2, 3, 22, 22]);
+        assert_eq!(
+            assemble(".const ONE = 1\nOUT rb-ONE+1\nOUT rb-ONE").unwrap(),
+            vec![204, 0, 204, -1]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(assemble("NOP").is_err());
+        assert!(assemble("ADD #1, #2").is_err());
+        assert!(assemble("ADD #1, #2, #3").is_err());
+        assert!(assemble("JT #1, #nowhere").is_err());
+        assert!(assemble("a: HLT\na: HLT").is_err());
+        assert!(assemble("OUT 5").is_err());
+        assert!(assemble(".const X = 9223372036854775807\n.data X+1").is_err());
+    }
+}
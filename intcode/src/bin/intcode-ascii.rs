@@ -0,0 +1,40 @@
+// Runs a text-based Intcode program in the terminal: lines typed on stdin become its input,
+// its characters go to stdout and any other values it outputs are listed when it halts.
+//
+// usage: intcode-ascii <program file>
+
+use std::io;
+use std::path::Path;
+
+use intcode::ascii;
+use intcode::loader::{self, Source};
+
+const USAGE: &str = "usage: intcode-ascii <program file>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let program = match args.as_slice() {
+        [path] if !path.starts_with("--") => loader::load(Source::Path(Path::new(path)))
+            .unwrap_or_else(|e| {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match ascii::interactive(&program, io::stdin().lock(), io::stdout()) {
+        Ok(values) => {
+            for value in values {
+                println!("{}", value);
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Step-through debugger for Intcode programs.
+//
+// usage: intcode-debugger <program file>
+//        intcode-debugger --resume <session file>
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use intcode::debugger::{Debugger, HELP};
+use intcode::loader::{self, Source};
+use intcode::session::Session;
+
+const USAGE: &str =
+    "usage: intcode-debugger <program file>\n       intcode-debugger --resume <session file>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut debugger = match args.as_slice() {
+        [flag, path] if flag == "--resume" => {
+            let session = Session::load(path).unwrap_or_else(|e| {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            });
+            println!(
+                "resumed after {} outputs: {:?}",
+                session.output.len(),
+                session.output
+            );
+            Debugger::from_session(&session)
+        }
+        [path] if !path.starts_with("--") => {
+            let program = loader::load(Source::Path(Path::new(path))).unwrap_or_else(|e| {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            });
+            Debugger::new(&program)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut last_command = String::new();
+
+    println!("{}\n", HELP);
+    print!("(icdb) ");
+    io::stdout().flush().expect("prompt written");
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("command read");
+        let command = match line.trim() {
+            // an empty line repeats the previous command
+            "" => last_command.clone(),
+            command => command.to_string(),
+        };
+
+        match command.as_str() {
+            "q" | "quit" => break,
+            "" => {}
+            _ => match debugger.execute(&command) {
+                Ok(reply) => println!("{}", reply),
+                Err(e) => println!("error: {}", e),
+            },
+        }
+
+        last_command = command;
+        print!("(icdb) ");
+        io::stdout().flush().expect("prompt written");
+    }
+}
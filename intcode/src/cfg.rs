@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::computer::{parse_instruction, InstructionArgumentMode, InstructionType};
+use crate::disassembler::{decode, Line};
+
+// Where control goes when a block ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Successor {
+    // the block starting at this address
+    Block(usize),
+    // a jump to an address outside the program, like the 99999 error traps of day5
+    Outside(i64),
+    // a jump whose target is read from memory at run time
+    Indirect,
+    // an address inside the program that does not decode as an instruction
+    Invalid(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    // falling through to the next instruction, also a conditional jump not taken
+    Next,
+    // a jump taken
+    Jump,
+}
+
+// Straight-line code entered only at its first instruction and left only after its last.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    // none for a block ending in HLT
+    pub successors: Vec<(EdgeKind, Successor)>,
+}
+
+// Control-flow graph of the code reachable from address 0, found by decoding the program image
+// without running it. Self-modifying programs are analysed as loaded.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cfg {
+    // blocks by start address
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+// Cells that straight-line code stored constants to, with every value they can hold where paths
+// with different constants meet. A cell missing from the map may hold anything.
+type Known = BTreeMap<usize, BTreeSet<i64>>;
+
+// a cell with more possible values than this is treated as unknown
+const MAX_VALUES: usize = 16;
+
+// One reachable instruction: its disassembly, length and how control leaves it if not by
+// falling through to the next decoded instruction.
+struct Decoded {
+    line: Line,
+    len: usize,
+    exits: Option<Vec<(EdgeKind, Successor)>>,
+    // the known cells after it runs, see `decode_at`
+    known: Known,
+}
+
+impl Cfg {
+    pub fn build(memory: &[i64]) -> Self {
+        let mut decoded: BTreeMap<usize, Decoded> = BTreeMap::new();
+        // known cells on entry to each address, over every path reaching it so far
+        let mut entries: BTreeMap<usize, Known> = BTreeMap::new();
+        let mut pending = vec![];
+
+        if decode(memory, 0).is_some() {
+            entries.insert(0, Known::new());
+            pending.push(0);
+        }
+
+        // decode until no address is reached with less known than it was decoded with
+        while let Some(address) = pending.pop() {
+            let instruction = decode_at(memory, address, &entries[&address]);
+            let targets: Vec<usize> = match &instruction.exits {
+                Some(exits) => exits
+                    .iter()
+                    .filter_map(|&(_, successor)| match successor {
+                        Successor::Block(target) => Some(target),
+                        _ => None,
+                    })
+                    .collect(),
+                None => vec![address + instruction.len],
+            };
+
+            for target in targets {
+                let entry = match entries.get(&target) {
+                    Some(entry) => join(entry, &instruction.known),
+                    None => instruction.known.clone(),
+                };
+                if entries.get(&target) != Some(&entry) {
+                    entries.insert(target, entry);
+                    pending.push(target);
+                }
+            }
+            decoded.insert(address, instruction);
+        }
+
+        // edges found with states that were later joined away are gone from the final decoding,
+        // so the blocks are laid out from what it still reaches
+        let mut leaders = BTreeSet::new();
+        let mut reached = BTreeSet::new();
+
+        if decoded.contains_key(&0) {
+            leaders.insert(0);
+            pending.push(0);
+        }
+
+        while let Some(address) = pending.pop() {
+            if !reached.insert(address) {
+                continue;
+            }
+
+            let instruction = &decoded[&address];
+            match &instruction.exits {
+                Some(exits) => {
+                    for &(_, successor) in exits {
+                        if let Successor::Block(target) = successor {
+                            leaders.insert(target);
+                            pending.push(target);
+                        }
+                    }
+                }
+                None => pending.push(address + instruction.len),
+            }
+        }
+
+        let blocks = leaders
+            .iter()
+            .map(|&start| {
+                let mut lines = vec![];
+                let mut address = start;
+
+                let successors = loop {
+                    let instruction = &decoded[&address];
+                    lines.push(instruction.line.clone());
+
+                    if let Some(exits) = &instruction.exits {
+                        break exits.clone();
+                    }
+                    address += instruction.len;
+                    if leaders.contains(&address) {
+                        break vec![(EdgeKind::Next, Successor::Block(address))];
+                    }
+                };
+
+                (
+                    start,
+                    Block {
+                        start,
+                        lines,
+                        successors,
+                    },
+                )
+            })
+            .collect();
+
+        Cfg { blocks }
+    }
+
+    // Graphviz source, one box per block listing its instructions:
+    //     cargo run -- --cfg | dot -Tsvg > cfg.svg
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        let mut extra = BTreeSet::new();
+
+        for block in self.blocks.values() {
+            let label: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", line))
+                .collect();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+
+        for block in self.blocks.values() {
+            for &(kind, successor) in &block.successors {
+                let style = match kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                };
+                writeln!(dot, "    b{} -> {}{};", block.start, node(successor), style).unwrap();
+
+                if !matches!(successor, Successor::Block(_)) {
+                    extra.insert(successor);
+                }
+            }
+        }
+
+        for successor in extra {
+            let label = match successor {
+                Successor::Outside(address) => address.to_string(),
+                Successor::Indirect => "?".to_string(),
+                Successor::Invalid(address) => format!("{}: invalid", address),
+                Successor::Block(_) => unreachable!(),
+            };
+            writeln!(
+                dot,
+                "    {} [label=\"{}\", shape=octagon];",
+                node(successor),
+                label
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn node(successor: Successor) -> String {
+    match successor {
+        Successor::Block(address) => format!("b{}", address),
+        Successor::Outside(address) if address < 0 => format!("outside_{}", address.unsigned_abs()),
+        Successor::Outside(address) => format!("outside{}", address),
+        Successor::Indirect => "indirect".to_string(),
+        Successor::Invalid(address) => format!("invalid{}", address),
+    }
+}
+
+// The instruction at `address`, which decodes. `known` are the cells that code leading here
+// stored constants to, like the return address day5 stores before jumping through it; they resolve
+// jumps and conditions read from those cells.
+fn decode_at(memory: &[i64], address: usize, known: &Known) -> Decoded {
+    let line = decode(&memory[address..], address).expect("reachable instruction decodes");
+    let instruction = parse_instruction(address, memory[address]).expect("decoded");
+    let len = 1 + instruction.opcode.arg_count();
+    let next = address + len;
+    let param = |n: usize| memory[address + 1 + n];
+    let values = |n: usize| match instruction.arg_modes[n] {
+        InstructionArgumentMode::IMMEDIATE => Some(BTreeSet::from([param(n)])),
+        InstructionArgumentMode::POSITION => usize::try_from(param(n))
+            .ok()
+            .and_then(|cell| known.get(&cell).cloned()),
+        InstructionArgumentMode::RELATIVE => None,
+    };
+
+    let exits = match instruction.opcode {
+        InstructionType::HALT => Some(vec![]),
+        InstructionType::JUMP_IF_TRUE | InstructionType::JUMP_IF_FALSE => {
+            let jumps_on = instruction.opcode == InstructionType::JUMP_IF_TRUE;
+            // a condition that is the same on every path makes the branch unconditional
+            let (jumps, falls) = match values(0) {
+                Some(values) => (
+                    values.iter().any(|&value| (value != 0) == jumps_on),
+                    values.iter().any(|&value| (value != 0) != jumps_on),
+                ),
+                None => (true, true),
+            };
+
+            let mut exits = vec![];
+            if jumps {
+                match values(1) {
+                    Some(targets) => exits.extend(
+                        targets
+                            .into_iter()
+                            .map(|target| (EdgeKind::Jump, successor(memory, target))),
+                    ),
+                    None => exits.push((EdgeKind::Jump, Successor::Indirect)),
+                }
+            }
+            if falls {
+                exits.push((EdgeKind::Next, successor(memory, next as i64)));
+            }
+            Some(exits)
+        }
+        // running into something that is not code ends the block
+        _ => match successor(memory, next as i64) {
+            Successor::Block(_) => None,
+            other => Some(vec![(EdgeKind::Next, other)]),
+        },
+    };
+
+    let mut known = known.clone();
+    if let Some(n) = instruction.opcode.write_arg() {
+        match (&instruction.arg_modes[n], usize::try_from(param(n))) {
+            (InstructionArgumentMode::POSITION, Ok(cell)) => {
+                let fold: Option<fn(i64, i64) -> Option<i64>> = match instruction.opcode {
+                    // a result that overflows faults at run time, the cell is not stored to
+                    InstructionType::ADD => Some(i64::checked_add),
+                    InstructionType::MULTIPLY => Some(i64::checked_mul),
+                    InstructionType::LESS_THAN => Some(|a, b| Some((a < b) as i64)),
+                    InstructionType::EQUALS => Some(|a, b| Some((a == b) as i64)),
+                    _ => None,
+                };
+                let results = match (fold, values(0), values(1)) {
+                    (Some(fold), Some(a), Some(b)) => a
+                        .iter()
+                        .flat_map(|&a| b.iter().map(move |&b| fold(a, b)))
+                        .collect::<Option<BTreeSet<i64>>>(),
+                    _ => None,
+                };
+
+                match results {
+                    Some(results) if results.len() <= MAX_VALUES => known.insert(cell, results),
+                    _ => known.remove(&cell),
+                };
+            }
+            // a write relative to the base could land anywhere
+            _ => known.clear(),
+        }
+    }
+
+    Decoded {
+        line,
+        len,
+        exits,
+        known,
+    }
+}
+
+// What is known where two paths meet: the cells known on both, holding a value from either.
+fn join(a: &Known, b: &Known) -> Known {
+    a.iter()
+        .filter_map(|(cell, values)| {
+            let values: BTreeSet<i64> = values.union(b.get(cell)?).copied().collect();
+            (values.len() <= MAX_VALUES).then_some((*cell, values))
+        })
+        .collect()
+}
+
+fn successor(memory: &[i64], address: i64) -> Successor {
+    if address < 0 || address as usize >= memory.len() {
+        return Successor::Outside(address);
+    }
+
+    let address = address as usize;
+    match decode(&memory[address..], address) {
+        Some(_) => Successor::Block(address),
+        None => Successor::Invalid(address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::{Cfg, EdgeKind, Successor};
+
+    // reads a number, outputs 1 if it is 8 and 0 otherwise, skipping over an error trap
+    fn compare_with_8() -> Vec<i64> {
+        vec![
+            3, 21, 8, 21, 22, 23, 1005, 23, 16, 104, 0, 99, //
+            1106, 0, 99999, 99, //
+            104, 1, 1105, 1, 11, //
+            0, 8, 0,
+        ]
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let cfg = Cfg::build(&compare_with_8());
+        let edges: Vec<(usize, Vec<(EdgeKind, Successor)>)> = cfg
+            .blocks
+            .values()
+            .map(|block| (block.start, block.successors.clone()))
+            .collect();
+
+        assert_eq!(
+            edges,
+            vec![
+                (
+                    0,
+                    vec![
+                        (EdgeKind::Jump, Successor::Block(16)),
+                        (EdgeKind::Next, Successor::Block(9)),
+                    ]
+                ),
+                (9, vec![(EdgeKind::Next, Successor::Block(11))]),
+                (11, vec![]),
+                (16, vec![(EdgeKind::Jump, Successor::Block(11))]),
+            ]
+        );
+        assert_eq!(cfg.blocks[&0].lines.len(), 3);
+    }
+
+    #[test]
+    fn traps_and_indirect_jumps() {
+        // JF #0 always jumps to the trap, the code after it is never reached
+        let cfg = Cfg::build(&[1106, 0, 99999, 99]);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![(EdgeKind::Jump, Successor::Outside(99999))]
+        );
+
+        let cfg = Cfg::build(&[1105, 1, i64::MIN]);
+        assert!(cfg
+            .to_dot()
+            .contains("b0 -> outside_9223372036854775808 [label=\"jump\"];"));
+
+        // JT [5], [6] and a MUL running into a cell that does not decode
+        let cfg = Cfg::build(&[5, 5, 6, 1102, 2, 2, 7, 33]);
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![
+                (EdgeKind::Jump, Successor::Indirect),
+                (EdgeKind::Next, Successor::Block(3)),
+            ]
+        );
+        assert_eq!(
+            cfg.blocks[&3].successors,
+            vec![(EdgeKind::Next, Successor::Invalid(7))]
+        );
+    }
+
+    #[test]
+    fn constant_return_address() {
+        // stores 8 to [0] and jumps through it, like day5 calling and returning from a subroutine
+        let cfg = Cfg::build(&[1101, 4, 4, 0, 105, 1, 0, 99, 99]);
+
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![(EdgeKind::Jump, Successor::Block(8))]
+        );
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 8]);
+    }
+
+    #[test]
+    fn overflow_is_unknown() {
+        let cfg = Cfg::build(&[1101, i64::MAX, 1, 5, 99, 0]);
+        assert_eq!(cfg.blocks[&0].lines.len(), 2);
+
+        // the MUL overflows, so the JT after it reads an unknown target
+        let cfg = Cfg::build(&[1102, i64::MAX, 2, 8, 105, 1, 8, 99, 0]);
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![(EdgeKind::Jump, Successor::Indirect)]
+        );
+    }
+
+    #[test]
+    fn return_address_from_two_callers() {
+        // both paths store a different return address to [100] before reaching the JT at 7
+        let cfg = Cfg::build(&[
+            1005, 50, 11, // 0: JT [50], #11
+            1101, 18, 0, 100, // 3: [100] = 18
+            105, 1, 100, // 7: JT #1, [100]
+            99,  // 10: HLT
+            1101, 10, 0, 100, // 11: [100] = 10
+            1105, 1, 7, // 15: JT #1, #7
+            104, 1, 99, // 18: OUT #1, HLT
+        ]);
+
+        assert_eq!(
+            cfg.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![0, 3, 7, 10, 11, 18]
+        );
+        assert_eq!(
+            cfg.blocks[&7].successors,
+            vec![
+                (EdgeKind::Jump, Successor::Block(10)),
+                (EdgeKind::Jump, Successor::Block(18)),
+            ]
+        );
+        assert_eq!(
+            cfg.blocks[&3].successors,
+            vec![(EdgeKind::Next, Successor::Block(7))]
+        );
+    }
+
+    #[test]
+    fn dot() {
+        let cfg = Cfg::build(&[3, 7, 1005, 7, 6, 99, 1106, 0, 99999]);
+
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph cfg {\n    node [shape=box, fontname=monospace];\n    \
+             b0 [label=\"    0: IN [7]\\l    2: JT [7], #6\\l\"];\n    \
+             b5 [label=\"    5: HLT\\l\"];\n    \
+             b6 [label=\"    6: JF #0, #99999\\l\"];\n    \
+             b0 -> b6 [label=\"jump\"];\n    \
+             b0 -> b5;\n    \
+             b6 -> outside99999 [label=\"jump\"];\n    \
+             outside99999 [label=\"99999\", shape=octagon];\n}\n"
+        );
+    }
+}
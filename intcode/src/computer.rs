@@ -0,0 +1,1152 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::disassembler::decode;
+use crate::error::IntcodeError;
+use crate::io::{Input, Output};
+use crate::memory::Memory;
+use crate::profile::Profile;
+use crate::trace::{TraceEvent, Tracer};
+
+mod fast;
+
+use fast::DecodeCache;
+
+pub struct ProgramState<'a> {
+    memory: Memory,
+    memory_pos: usize,
+    relative_base: i64,
+    input: Box<dyn Input + 'a>,
+    // values fed by the caller while the machine was waiting for input, consumed before `input`
+    pending_input: VecDeque<i64>,
+    // hand every output value back to the caller of `run` instead of the output sink
+    pause_on_output: bool,
+
+    // number of values read from input and written to output so far
+    input_pos: usize,
+    output: Box<dyn Output + 'a>,
+    output_pos: usize,
+
+    // number of instructions executed so far
+    steps: u64,
+    tracer: Option<Box<dyn Tracer + 'a>>,
+    // memory writes of the current instruction, only collected while tracing
+    write_log: Option<Vec<(usize, i64)>>,
+
+    // instruction budget, `step` fails once this many instructions have executed
+    step_limit: Option<u64>,
+    // every state seen before an instruction and the step it was seen at, only while detecting loops
+    seen_states: Option<HashMap<MachineState, u64>>,
+
+    engine: Engine,
+    // instructions decoded by Engine::Fast
+    cache: DecodeCache,
+
+    profile: Option<Profile>,
+}
+
+// How `ProgramState::run` executes instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    // decodes every instruction as it is executed
+    #[default]
+    Reference,
+    // keeps decoded instructions until the program overwrites them; tracing and loop detection
+    // need the reference interpreter and fall back to it
+    Fast,
+}
+
+// A machine frozen between two instructions, without its input, output and tracer.
+// Memory pages are shared with the machine until either side writes, so taking one is cheap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    memory: Memory,
+    #[serde(rename = "ip")]
+    memory_pos: usize,
+    relative_base: i64,
+    pending_input: VecDeque<i64>,
+    input_pos: usize,
+    output_pos: usize,
+    steps: u64,
+}
+
+// Everything that decides what a machine does next apart from input values it has not read yet.
+// Output is excluded, a program repeating a state while writing output still never halts.
+#[derive(PartialEq, Eq, Hash)]
+struct MachineState {
+    ip: usize,
+    relative_base: i64,
+    input_pos: usize,
+    dense: Vec<i64>,
+    sparse: Vec<(usize, i64)>,
+}
+
+impl fmt::Debug for ProgramState<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramState")
+            .field("memory", &self.memory)
+            .field("memory_pos", &self.memory_pos)
+            .field("relative_base", &self.relative_base)
+            .field("pending_input", &self.pending_input)
+            .field("input_pos", &self.input_pos)
+            .field("output_pos", &self.output_pos)
+            .field("steps", &self.steps)
+            .field("step_limit", &self.step_limit)
+            .field("engine", &self.engine)
+            .finish_non_exhaustive()
+    }
+}
+
+// Opcode 3 takes a single integer as input and saves it to the position given by its only parameter. For example, the instruction 3,50 would take an input value and store it at address 50.
+// Opcode 4 outputs the value of its only parameter. For example, the instruction 4,50 would output the value at address 50.
+// Opcode 5 is jump-if-true: if the first parameter is non-zero, it sets the instruction pointer to the value from the second parameter. Otherwise, it does nothing.
+// Opcode 6 is jump-if-false: if the first parameter is zero, it sets the instruction pointer to the value from the second parameter. Otherwise, it does nothing.
+// Opcode 7 is less than: if the first parameter is less than the second parameter, it stores 1 in the position given by the third parameter. Otherwise, it stores 0.
+// Opcode 8 is equals: if the first parameter is equal to the second parameter, it stores 1 in the position given by the third parameter. Otherwise, it stores 0.
+// Opcode 9 adjusts the relative base by the value of its only parameter. The relative base increases (or decreases, if the value is negative) by the value of the parameter.
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InstructionType {
+    ADD,
+    MULTIPLY,
+    HALT,
+    READ,
+    WRITE,
+    JUMP_IF_TRUE,
+    JUMP_IF_FALSE,
+    LESS_THAN,
+    EQUALS,
+    ADJUST_RELATIVE_BASE,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InstructionArgumentMode {
+    POSITION,
+    IMMEDIATE,
+    RELATIVE,
+}
+
+impl InstructionType {
+    // number of parameters following the opcode
+    pub(crate) fn arg_count(&self) -> usize {
+        match self {
+            InstructionType::ADD
+            | InstructionType::MULTIPLY
+            | InstructionType::LESS_THAN
+            | InstructionType::EQUALS => 3,
+            InstructionType::JUMP_IF_TRUE | InstructionType::JUMP_IF_FALSE => 2,
+            InstructionType::READ
+            | InstructionType::WRITE
+            | InstructionType::ADJUST_RELATIVE_BASE => 1,
+            InstructionType::HALT => 0,
+        }
+    }
+
+    // index of the parameter the instruction stores its result through, if any
+    pub(crate) fn write_arg(&self) -> Option<usize> {
+        match self {
+            InstructionType::ADD
+            | InstructionType::MULTIPLY
+            | InstructionType::LESS_THAN
+            | InstructionType::EQUALS => Some(2),
+            InstructionType::READ => Some(0),
+            _ => None,
+        }
+    }
+}
+
+// Why `ProgramState::run` stopped.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    // READ found no input, the instruction pointer stays on it so `run` retries after `feed`
+    NeedsInput,
+    // only returned when pausing on output is enabled
+    Output(i64),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub(crate) opcode: InstructionType,
+    pub(crate) arg_modes: Vec<InstructionArgumentMode>,
+}
+
+impl Instruction {
+    // executes the instruction, returns the status to stop with or None to continue
+    pub fn run(&self, state: &mut ProgramState) -> Result<Option<Status>, IntcodeError> {
+        match self.opcode {
+            InstructionType::ADD => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+                let c = self.address(state, 2)?;
+
+                let value = a.checked_add(b).ok_or_else(|| state.overflow())?;
+
+                state.store(c, value);
+                state.memory_pos += 4;
+
+                Ok(None)
+            }
+            InstructionType::MULTIPLY => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+                let c = self.address(state, 2)?;
+
+                let value = a.checked_mul(b).ok_or_else(|| state.overflow())?;
+
+                state.store(c, value);
+                state.memory_pos += 4;
+
+                Ok(None)
+            }
+            InstructionType::JUMP_IF_TRUE => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+
+                if a != 0 {
+                    state.memory_pos = state.target(b)?;
+                } else {
+                    state.memory_pos += 3;
+                }
+
+                Ok(None)
+            }
+            InstructionType::JUMP_IF_FALSE => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+
+                if a == 0 {
+                    state.memory_pos = state.target(b)?;
+                } else {
+                    state.memory_pos += 3;
+                }
+
+                Ok(None)
+            }
+            InstructionType::LESS_THAN => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+                let c = self.address(state, 2)?;
+
+                state.store(c, if a < b { 1 } else { 0 });
+                state.memory_pos += 4;
+
+                Ok(None)
+            }
+            InstructionType::EQUALS => {
+                let a = self.argument(state, 0)?;
+                let b = self.argument(state, 1)?;
+                let c = self.address(state, 2)?;
+
+                state.store(c, if a == b { 1 } else { 0 });
+                state.memory_pos += 4;
+
+                Ok(None)
+            }
+            InstructionType::READ => {
+                let addr = self.address(state, 0)?;
+                // read one input
+                let val = match state.next_input() {
+                    Some(val) => val,
+                    None => return Ok(Some(Status::NeedsInput)),
+                };
+
+                state.store(addr, val);
+                state.input_pos += 1;
+                state.memory_pos += 2;
+
+                Ok(None)
+            }
+            InstructionType::WRITE => {
+                let val = self.argument(state, 0)?;
+
+                if !state.pause_on_output {
+                    state.emit(state.memory_pos, val)?;
+                }
+                state.output_pos += 1;
+                state.memory_pos += 2;
+
+                if state.pause_on_output {
+                    return Ok(Some(Status::Output(val)));
+                }
+
+                Ok(None)
+            }
+            InstructionType::ADJUST_RELATIVE_BASE => {
+                let a = self.argument(state, 0)?;
+
+                state.relative_base = state.relative(a)?;
+                state.memory_pos += 2;
+
+                Ok(None)
+            }
+            InstructionType::HALT => Ok(Some(Status::Halted)),
+        }
+    }
+
+    // the n-th parameter of the instruction resolved according to its mode,
+    // the only place where parameter modes are interpreted
+    fn operand(&self, state: &ProgramState, n: usize) -> Result<Operand, IntcodeError> {
+        let param = state.memory[state.memory_pos + 1 + n];
+
+        match self.arg_modes[n] {
+            InstructionArgumentMode::IMMEDIATE => Ok(Operand::Value(param)),
+            InstructionArgumentMode::POSITION => Ok(Operand::Address(state.target(param)?)),
+            InstructionArgumentMode::RELATIVE => {
+                Ok(Operand::Address(state.target(state.relative(param)?)?))
+            }
+        }
+    }
+
+    // value of the n-th parameter
+    fn argument(&self, state: &ProgramState, n: usize) -> Result<i64, IntcodeError> {
+        match self.operand(state, n)? {
+            Operand::Value(val) => Ok(val),
+            Operand::Address(addr) => Ok(state.memory[addr]),
+        }
+    }
+
+    // memory address the n-th parameter writes to
+    fn address(&self, state: &ProgramState, n: usize) -> Result<usize, IntcodeError> {
+        match self.operand(state, n)? {
+            Operand::Address(addr) => Ok(addr),
+            Operand::Value(_) => Err(IntcodeError::InvalidMode {
+                ip: state.memory_pos,
+                instruction: state.code(),
+                parameter: n,
+                mode: 1,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Operand {
+    Value(i64),
+    Address(usize),
+}
+
+// ABCDE
+//  1002
+// DE - two-digit opcode,      02 == opcode 2
+//  C - mode of 1st parameter,  0 == position mode
+//  B - mode of 2nd parameter,  1 == immediate mode
+//  A - mode of 3rd parameter,  0 == position mode,
+//                                   omitted due to being a leading zero
+pub(crate) fn parse_instruction(ip: usize, code: i64) -> Result<Instruction, IntcodeError> {
+    let mut arg_modes = vec![];
+    for (parameter, mode) in mode_digits(code).into_iter().enumerate() {
+        arg_modes.push(parse_mode(mode).ok_or(IntcodeError::InvalidMode {
+            ip,
+            instruction: code,
+            parameter,
+            mode,
+        })?);
+    }
+
+    match parse_opcode(code) {
+        Some(opcode) => Ok(Instruction { opcode, arg_modes }),
+        None => Err(IntcodeError::InvalidOpcode {
+            ip,
+            instruction: code,
+        }),
+    }
+}
+
+// the C, B and A digits of an instruction, in parameter order
+pub(crate) fn mode_digits(code: i64) -> [i64; 3] {
+    [(code / 100) % 10, (code / 1000) % 10, (code / 10000) % 10]
+}
+
+// the DE digits of an instruction
+pub(crate) fn parse_opcode(code: i64) -> Option<InstructionType> {
+    match code % 100 {
+        1 => Some(InstructionType::ADD),
+        2 => Some(InstructionType::MULTIPLY),
+        3 => Some(InstructionType::READ),
+        4 => Some(InstructionType::WRITE),
+        5 => Some(InstructionType::JUMP_IF_TRUE),
+        6 => Some(InstructionType::JUMP_IF_FALSE),
+        7 => Some(InstructionType::LESS_THAN),
+        8 => Some(InstructionType::EQUALS),
+        9 => Some(InstructionType::ADJUST_RELATIVE_BASE),
+        99 => Some(InstructionType::HALT),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_mode(mode: i64) -> Option<InstructionArgumentMode> {
+    match mode {
+        0 => Some(InstructionArgumentMode::POSITION),
+        1 => Some(InstructionArgumentMode::IMMEDIATE),
+        2 => Some(InstructionArgumentMode::RELATIVE),
+        _ => None,
+    }
+}
+
+impl<'a> ProgramState<'a> {
+    pub fn next_instruction(&mut self) -> Result<Instruction, IntcodeError> {
+        parse_instruction(self.memory_pos, self.code())
+    }
+
+    // runs until the program halts, blocks on input or, if enabled, produces output;
+    // calling it again resumes where it stopped
+    pub fn run(&mut self) -> Result<Status, IntcodeError> {
+        if self.engine == Engine::Fast
+            && self.tracer.is_none()
+            && self.seen_states.is_none()
+            && self.profile.is_none()
+        {
+            return self.run_fast();
+        }
+
+        loop {
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
+
+    // runs a program that is given all of its input up front to HALT, running out of input is
+    // an error; values returned while pausing on output are passed on to the output sink
+    pub fn run_to_halt(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.run()? {
+                Status::Halted => return Ok(()),
+                Status::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        ip: self.memory_pos,
+                        instruction: self.code(),
+                    })
+                }
+                // the WRITE already advanced ip past its single parameter
+                Status::Output(value) => self.emit(self.memory_pos - 2, value)?,
+            }
+        }
+    }
+
+    // decodes and executes a single instruction, returns the status to stop with or None
+    pub fn step(&mut self) -> Result<Option<Status>, IntcodeError> {
+        let instruction = self.next_instruction()?;
+        let ip = self.memory_pos;
+
+        if let Some(limit) = self.step_limit {
+            if self.steps >= limit {
+                return Err(IntcodeError::StepLimitExceeded {
+                    ip,
+                    instruction: self.code(),
+                    limit,
+                });
+            }
+        }
+        let state = self.seen_states.as_ref().map(|_| self.machine_state());
+        if let (Some(state), Some(seen)) = (&state, &self.seen_states) {
+            if let Some(&first_seen) = seen.get(state) {
+                return Err(IntcodeError::LoopDetected {
+                    ip,
+                    instruction: self.code(),
+                    first_seen,
+                    step: self.steps,
+                });
+            }
+        }
+
+        let event = self.tracer.as_ref().map(|_| self.trace_event(&instruction));
+        let reads = self.profile.as_ref().map(|_| self.reads(&instruction));
+        if event.is_some() {
+            self.write_log = Some(vec![]);
+        }
+
+        let status = instruction.run(self)?;
+
+        // a READ without input did not execute, it runs again once input arrives
+        if status == Some(Status::NeedsInput) {
+            self.write_log = None;
+            return Ok(status);
+        }
+
+        if let (Some(state), Some(seen)) = (state, self.seen_states.as_mut()) {
+            seen.insert(state, self.steps);
+        }
+
+        if let (Some(reads), Some(profile)) = (reads, self.profile.as_mut()) {
+            profile.record_execution(ip, &instruction.opcode, &reads);
+        }
+
+        if let (Some(mut event), Some(tracer)) = (event, self.tracer.as_mut()) {
+            event.writes = self.write_log.take().unwrap_or_default();
+            tracer
+                .trace(&event)
+                .map_err(|e| IntcodeError::TraceFailed {
+                    ip,
+                    instruction: event.code,
+                    reason: e.to_string(),
+                })?;
+        }
+        self.steps += 1;
+
+        Ok(status)
+    }
+
+    fn machine_state(&self) -> MachineState {
+        let (dense, sparse) = self.memory.cells();
+
+        MachineState {
+            ip: self.memory_pos,
+            relative_base: self.relative_base,
+            input_pos: self.input_pos,
+            dense,
+            sparse,
+        }
+    }
+
+    // raw opcode cell at ip
+    fn code(&self) -> i64 {
+        self.memory[self.memory_pos]
+    }
+
+    // converts a computed address or jump target, faulting on negative values
+    fn target(&self, address: i64) -> Result<usize, IntcodeError> {
+        usize::try_from(address).map_err(|_| IntcodeError::NegativeAddress {
+            ip: self.memory_pos,
+            instruction: self.code(),
+            address,
+        })
+    }
+
+    // relative base plus offset, faulting when it does not fit in an i64
+    fn relative(&self, offset: i64) -> Result<i64, IntcodeError> {
+        self.relative_base
+            .checked_add(offset)
+            .ok_or_else(|| self.overflow())
+    }
+
+    // the instruction at ip computed a result that does not fit in an i64
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::ArithmeticOverflow {
+            ip: self.memory_pos,
+            instruction: self.code(),
+        }
+    }
+
+    // hands the result of the WRITE at ip to the output sink
+    fn emit(&mut self, ip: usize, value: i64) -> Result<(), IntcodeError> {
+        self.output
+            .write(value)
+            .map_err(|_| IntcodeError::OutputOverflow {
+                ip,
+                instruction: self.memory[ip],
+                value,
+            })
+    }
+
+    // everything about the instruction at ip that is known before it executes
+    fn trace_event(&self, instruction: &Instruction) -> TraceEvent {
+        let ip = self.memory_pos;
+        let cells: Vec<i64> = (ip..=ip + instruction.opcode.arg_count())
+            .map(|addr| self.memory[addr])
+            .collect();
+
+        let operands = (0..instruction.opcode.arg_count())
+            .map(|n| match instruction.operand(self, n) {
+                Ok(Operand::Value(val)) => val,
+                Ok(Operand::Address(addr)) if instruction.opcode.write_arg() == Some(n) => {
+                    addr as i64
+                }
+                Ok(Operand::Address(addr)) => self.memory[addr],
+                // the instruction faults on the same operand before the event is reported
+                Err(_) => 0,
+            })
+            .collect();
+
+        TraceEvent {
+            step: self.steps,
+            ip,
+            relative_base: self.relative_base,
+            code: cells[0],
+            instruction: decode(&cells, ip)
+                .map(|line| line.body())
+                .unwrap_or_default(),
+            operands,
+            writes: vec![],
+        }
+    }
+
+    // cells the instruction at ip reads through its position and relative parameters
+    fn reads(&self, instruction: &Instruction) -> Vec<usize> {
+        (0..instruction.opcode.arg_count())
+            .filter(|&n| instruction.opcode.write_arg() != Some(n))
+            .filter_map(|n| match instruction.operand(self, n) {
+                Ok(Operand::Address(addr)) => Some(addr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // stores an instruction result, recording it for the tracer and the profile
+    fn store(&mut self, addr: usize, value: i64) {
+        self.memory[addr] = value;
+        self.cache.invalidate(addr);
+
+        if let Some(log) = self.write_log.as_mut() {
+            log.push((addr, value));
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record_write(addr);
+        }
+    }
+
+    // values fed by the caller first, then the input source
+    fn next_input(&mut self) -> Option<i64> {
+        match self.pending_input.pop_front() {
+            Some(value) => Some(value),
+            None => self.input.read(),
+        }
+    }
+
+    // every executed instruction is reported to the tracer from now on
+    pub fn set_tracer(&mut self, tracer: impl Tracer + 'a) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    // `step` fails with StepLimitExceeded instead of executing instruction number `limit + 1`
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    // `step` fails with LoopDetected when the machine is about to execute from a state it was
+    // already in; every state is kept, so this costs a copy of memory per instruction
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.seen_states = if enabled { Some(HashMap::new()) } else { None };
+    }
+
+    // counts executions per address and opcode and reads and writes per cell from now on,
+    // disabling it drops the counts
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = if enabled {
+            Some(Profile::default())
+        } else {
+            None
+        };
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    // number of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn ip(&self) -> usize {
+        self.memory_pos
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    // number of values read from input so far
+    pub fn input_pos(&self) -> usize {
+        self.input_pos
+    }
+
+    // number of values written to output so far
+    pub fn output_pos(&self) -> usize {
+        self.output_pos
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<i64> {
+        &self.pending_input
+    }
+
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.memory[addr]
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i64) {
+        self.memory[addr] = value;
+        self.cache.invalidate(addr);
+    }
+
+    // queues a value for READ, typically after `run` returned `Status::NeedsInput`
+    pub fn feed(&mut self, value: i64) {
+        self.pending_input.push_back(value);
+    }
+
+    pub fn set_pause_on_output(&mut self, pause: bool) {
+        self.pause_on_output = pause;
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    // the loaded image including every cell written past its end, except far sparse writes
+    pub fn memory(&self) -> Vec<i64> {
+        self.memory.image()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            memory_pos: self.memory_pos,
+            relative_base: self.relative_base,
+            pending_input: self.pending_input.clone(),
+            input_pos: self.input_pos,
+            output_pos: self.output_pos,
+            steps: self.steps,
+        }
+    }
+
+    // rewinds, or fast-forwards, the machine to `snapshot`; input, output, tracer and limits stay
+    // as they are and states seen by the loop detector are forgotten
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory.clone();
+        self.memory_pos = snapshot.memory_pos;
+        self.relative_base = snapshot.relative_base;
+        self.pending_input = snapshot.pending_input.clone();
+        self.input_pos = snapshot.input_pos;
+        self.output_pos = snapshot.output_pos;
+        self.steps = snapshot.steps;
+        self.cache.clear();
+
+        if let Some(seen) = self.seen_states.as_mut() {
+            seen.clear();
+        }
+    }
+
+    // a new machine continuing from `snapshot` with its own input and output
+    pub fn from_snapshot(
+        snapshot: &Snapshot,
+        input: impl Input + 'a,
+        output: impl Output + 'a,
+    ) -> Self {
+        let mut state = Self::new(&[], input, output);
+        state.restore(snapshot);
+        state
+    }
+
+    // a copy of this machine at its current instruction with its own input and output; pausing on
+    // output, the engine, the step limit and loop detection carry over, the tracer and the
+    // profile do not
+    pub fn fork<'b>(&self, input: impl Input + 'b, output: impl Output + 'b) -> ProgramState<'b> {
+        let mut fork = ProgramState::from_snapshot(&self.snapshot(), input, output);
+        fork.pause_on_output = self.pause_on_output;
+        fork.step_limit = self.step_limit;
+        fork.engine = self.engine;
+        fork.set_loop_detection(self.seen_states.is_some());
+        fork
+    }
+
+    pub fn new(program: &[i64], input: impl Input + 'a, output: impl Output + 'a) -> Self {
+        Self {
+            memory: Memory::new(program),
+            input: Box::new(input),
+            pending_input: VecDeque::new(),
+            pause_on_output: false,
+            output: Box::new(output),
+            memory_pos: 0,
+            relative_base: 0,
+            input_pos: 0,
+            output_pos: 0,
+            steps: 0,
+            tracer: None,
+            write_log: None,
+            step_limit: None,
+            seen_states: None,
+            engine: Engine::default(),
+            cache: DecodeCache::default(),
+            profile: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::mpsc;
+
+    use crate::computer::{
+        parse_instruction, Instruction, InstructionArgumentMode, InstructionType, ProgramState,
+        Status,
+    };
+    use crate::error::IntcodeError;
+
+    #[test]
+    fn parse_instructions() {
+        assert_eq!(
+            Instruction {
+                opcode: InstructionType::MULTIPLY,
+                arg_modes: vec![
+                    InstructionArgumentMode::POSITION,
+                    InstructionArgumentMode::IMMEDIATE,
+                    InstructionArgumentMode::POSITION,
+                ]
+            },
+            parse_instruction(0, 1002).unwrap()
+        )
+    }
+
+    #[test]
+    fn run_program() {
+        let input = VecDeque::new();
+        let mut output = vec![];
+        let memory = vec![1002, 4, 3, 4, 33];
+
+        let mut prog = ProgramState::new(&memory, input, &mut output);
+
+        match prog.run() {
+            Ok(_) => {}
+            Err(e) => panic!("error running program {:?}", e),
+        }
+        assert_eq!(prog.memory(), vec![1002, 4, 3, 4, 99])
+    }
+
+    fn run_with_input(memory: Vec<i64>, value: i64) -> i64 {
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::from(vec![value]), &mut output);
+
+        match prog.run() {
+            Ok(_) => {}
+            Err(e) => panic!("error running program {:?}", e),
+        }
+        drop(prog);
+
+        output[0]
+    }
+
+    #[test]
+    fn compare_instructions() {
+        // position mode, input equal to 8
+        let prog = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(run_with_input(prog.clone(), 8), 1);
+        assert_eq!(run_with_input(prog, 7), 0);
+
+        // immediate mode, input less than 8
+        let prog = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
+        assert_eq!(run_with_input(prog.clone(), 5), 1);
+        assert_eq!(run_with_input(prog, 8), 0);
+    }
+
+    #[test]
+    fn jump_instructions() {
+        // output 0 if the input was zero or 1 if the input was non-zero
+        let prog = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        assert_eq!(run_with_input(prog.clone(), 0), 0);
+        assert_eq!(run_with_input(prog, 3), 1);
+
+        let prog = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(run_with_input(prog.clone(), 0), 0);
+        assert_eq!(run_with_input(prog, 3), 1);
+    }
+
+    #[test]
+    fn parse_relative_mode() {
+        assert_eq!(
+            Instruction {
+                opcode: InstructionType::ADD,
+                arg_modes: vec![
+                    InstructionArgumentMode::RELATIVE,
+                    InstructionArgumentMode::IMMEDIATE,
+                    InstructionArgumentMode::RELATIVE,
+                ]
+            },
+            parse_instruction(0, 21201).unwrap()
+        );
+        assert!(parse_instruction(0, 301).is_err());
+    }
+
+    #[test]
+    fn relative_base() {
+        // base = 10, [base + 2] = [base + 0] + [base + 1], base += [base + 2]
+        let memory = vec![109, 10, 22201, 0, 1, 2, 209, 2, 99, 0, 7, 8, 0];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), &mut output);
+
+        match prog.run() {
+            Ok(_) => {}
+            Err(e) => panic!("error running program {:?}", e),
+        }
+        assert_eq!(prog.relative_base, 25);
+        assert_eq!(prog.peek(12), 15);
+    }
+
+    #[test]
+    fn memory_beyond_program() {
+        // [1000] = 6 * 7, [10000000] = [1000] + 0, output [10000000]
+        let memory = vec![1102, 6, 7, 1000, 1001, 1000, 0, 10000000, 4, 10000000, 99];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), &mut output);
+
+        match prog.run() {
+            Ok(_) => {}
+            Err(e) => panic!("error running program {:?}", e),
+        }
+        assert_eq!(prog.memory().len(), 1001);
+        drop(prog);
+
+        assert_eq!(output, vec![42]);
+    }
+
+    #[test]
+    fn read_write_modes() {
+        // output immediate 0, then the input stored relative to base 100
+        let memory = vec![104, 0, 109, 100, 203, 5, 204, 5, 99];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::from(vec![13]), &mut output);
+
+        match prog.run() {
+            Ok(_) => {}
+            Err(e) => panic!("error running program {:?}", e),
+        }
+        assert_eq!(prog.peek(105), 13);
+        drop(prog);
+
+        assert_eq!(output, vec![0, 13]);
+    }
+
+    #[test]
+    fn write_to_immediate() {
+        let memory = vec![11101, 1, 1, 0, 99];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), &mut output);
+
+        assert_eq!(
+            prog.run(),
+            Err(IntcodeError::InvalidMode {
+                ip: 0,
+                instruction: 11101,
+                parameter: 2,
+                mode: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn pause_on_input() {
+        // output the sum of two inputs
+        let memory = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::from(vec![2]), &mut output);
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        assert_eq!(prog.memory_pos, 2);
+
+        prog.feed(40);
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        drop(prog);
+
+        assert_eq!(output, vec![42]);
+    }
+
+    #[test]
+    fn pause_on_output() {
+        let memory = vec![104, 1, 104, 2, 99];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), &mut output);
+        prog.set_pause_on_output(true);
+
+        assert_eq!(prog.run().unwrap(), Status::Output(1));
+        assert_eq!(prog.run().unwrap(), Status::Output(2));
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        drop(prog);
+
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn large_numbers() {
+        let prog = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        assert_eq!(run_with_input(prog, 0), 1219070632396864);
+
+        let prog = vec![104, 1125899906842624, 99];
+        assert_eq!(run_with_input(prog, 0), 1125899906842624);
+    }
+
+    #[test]
+    fn faults() {
+        let run = |memory: Vec<i64>| {
+            let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+            prog.run_to_halt()
+        };
+
+        assert_eq!(
+            run(vec![1101, 1, 1, 5, 42]),
+            Err(IntcodeError::InvalidOpcode {
+                ip: 4,
+                instruction: 42,
+            })
+        );
+        assert_eq!(
+            run(vec![301, 0, 0, 0, 99]),
+            Err(IntcodeError::InvalidMode {
+                ip: 0,
+                instruction: 301,
+                parameter: 0,
+                mode: 3,
+            })
+        );
+        assert_eq!(
+            run(vec![109, -5, 204, 2, 99]),
+            Err(IntcodeError::NegativeAddress {
+                ip: 2,
+                instruction: 204,
+                address: -3,
+            })
+        );
+        assert_eq!(
+            run(vec![1105, 1, -1, 99]),
+            Err(IntcodeError::NegativeAddress {
+                ip: 0,
+                instruction: 1105,
+                address: -1,
+            })
+        );
+        assert_eq!(
+            run(vec![1102, i64::MAX, 2, 5, 99, 0]),
+            Err(IntcodeError::ArithmeticOverflow {
+                ip: 0,
+                instruction: 1102,
+            })
+        );
+        assert_eq!(
+            run(vec![1, 5, 6, 0, 99, i64::MIN, -1]),
+            Err(IntcodeError::ArithmeticOverflow {
+                ip: 0,
+                instruction: 1,
+            })
+        );
+        assert_eq!(
+            run(vec![109, i64::MAX, 109, 1, 99]),
+            Err(IntcodeError::ArithmeticOverflow {
+                ip: 2,
+                instruction: 109,
+            })
+        );
+        assert_eq!(
+            run(vec![109, i64::MAX, 204, 1, 99]),
+            Err(IntcodeError::ArithmeticOverflow {
+                ip: 2,
+                instruction: 204,
+            })
+        );
+        assert_eq!(
+            run(vec![3, 0, 99]),
+            Err(IntcodeError::InputExhausted {
+                ip: 0,
+                instruction: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn output_overflow() {
+        let memory = vec![104, 7, 99];
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), tx);
+
+        let err = prog.run().unwrap_err();
+        assert_eq!(
+            err,
+            IntcodeError::OutputOverflow {
+                ip: 0,
+                instruction: 104,
+                value: 7,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "output sink rejected value 7 at 0 (instruction 104)"
+        );
+        // the failed WRITE is retried on the next run
+        assert_eq!(prog.ip(), 0);
+    }
+
+    #[test]
+    fn step_limit() {
+        // jump to itself forever
+        let memory = vec![1105, 1, 0];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+        prog.set_step_limit(Some(100));
+
+        assert_eq!(
+            prog.run(),
+            Err(IntcodeError::StepLimitExceeded {
+                ip: 0,
+                instruction: 1105,
+                limit: 100,
+            })
+        );
+        assert_eq!(prog.steps(), 100);
+
+        let memory = vec![1101, 1, 1, 5, 99, 0];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+        prog.set_step_limit(Some(2));
+        assert_eq!(prog.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn loop_detection() {
+        // count [14] down from 3 to 0, reset it to 3 and start over:
+        // ADD [14], #-1, [14]; JT [14], #0; ADD #3, #0, [14]; JT #1, #0
+        let memory = vec![1001, 14, -1, 14, 1005, 14, 0, 1101, 3, 0, 14, 1105, 1, 0, 3];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+        prog.set_loop_detection(true);
+
+        assert_eq!(
+            prog.run(),
+            Err(IntcodeError::LoopDetected {
+                ip: 0,
+                instruction: 1001,
+                first_seen: 0,
+                step: 8,
+            })
+        );
+
+        // a READ waiting for input is not mistaken for a loop when it runs again
+        let memory = vec![3, 5, 3, 5, 99, 0];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+        prog.set_loop_detection(true);
+
+        assert_eq!(prog.run(), Ok(Status::NeedsInput));
+        prog.feed(1);
+        assert_eq!(prog.run(), Ok(Status::NeedsInput));
+        prog.feed(1);
+        assert_eq!(prog.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn snapshot_restore_and_fork() {
+        // output the sum of two inputs
+        let memory = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let mut prog = ProgramState::new(&memory, VecDeque::from(vec![2]), vec![]);
+        prog.set_pause_on_output(true);
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        let checkpoint = prog.snapshot();
+
+        prog.feed(40);
+        assert_eq!(prog.run().unwrap(), Status::Output(42));
+
+        let mut fork = prog.fork(VecDeque::new(), vec![]);
+        assert_eq!(fork.run().unwrap(), Status::Halted);
+
+        prog.restore(&checkpoint);
+        assert_eq!(prog.ip(), 2);
+        assert_eq!(prog.peek(13), 0);
+        prog.feed(5);
+        assert_eq!(prog.run().unwrap(), Status::Output(7));
+
+        let mut other = ProgramState::from_snapshot(&checkpoint, VecDeque::from(vec![-2]), vec![]);
+        other.set_pause_on_output(true);
+        assert_eq!(other.run().unwrap(), Status::Output(0));
+        assert_eq!(other.input_pos(), 2);
+    }
+}
@@ -0,0 +1,307 @@
+// Engine::Fast: instructions are decoded once into a cache indexed by address and executed without
+// allocating. Anything unusual, an undecodable instruction, tracing or loop detection, goes
+// through the reference interpreter so both engines fail and report in exactly the same way.
+
+use crate::computer::{
+    mode_digits, parse_mode, parse_opcode, InstructionArgumentMode, InstructionType, ProgramState,
+    Status,
+};
+use crate::error::IntcodeError;
+
+// addresses past this are decoded on every visit instead of cached
+const CACHE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Decoded {
+    op: InstructionType,
+    modes: [InstructionArgumentMode; 3],
+}
+
+impl Decoded {
+    // None for anything the reference interpreter rejects, including immediate mode writes
+    fn new(code: i64) -> Option<Self> {
+        let op = parse_opcode(code)?;
+        let [a, b, c] = mode_digits(code);
+        let modes = [parse_mode(a)?, parse_mode(b)?, parse_mode(c)?];
+
+        if let Some(n) = op.write_arg() {
+            if modes[n] == InstructionArgumentMode::IMMEDIATE {
+                return None;
+            }
+        }
+
+        Some(Self { op, modes })
+    }
+}
+
+// Decoded instructions by address, dropped whenever one of their cells is written.
+#[derive(Debug, Clone, Default)]
+pub(super) struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+}
+
+impl DecodeCache {
+    // instructions are at most four cells long, so a write to addr can only change the ones
+    // starting up to three cells before it
+    pub(super) fn invalidate(&mut self, addr: usize) {
+        let end = (addr + 1).min(self.entries.len());
+        for entry in &mut self.entries[addr.saturating_sub(3).min(end)..end] {
+            *entry = None;
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn get(&mut self, ip: usize, code: i64) -> Option<Decoded> {
+        if ip >= CACHE_LIMIT {
+            return Decoded::new(code);
+        }
+        if ip >= self.entries.len() {
+            self.entries.resize(ip + 1, None);
+        }
+
+        match self.entries[ip] {
+            Some(decoded) => Some(decoded),
+            None => {
+                self.entries[ip] = Decoded::new(code);
+                self.entries[ip]
+            }
+        }
+    }
+}
+
+impl ProgramState<'_> {
+    pub(super) fn run_fast(&mut self) -> Result<Status, IntcodeError> {
+        loop {
+            let ip = self.memory_pos;
+            let code = self.memory[ip];
+            let decoded = match self.cache.get(ip, code) {
+                Some(decoded) => decoded,
+                None => match self.step()? {
+                    Some(status) => return Ok(status),
+                    None => continue,
+                },
+            };
+
+            if let Some(limit) = self.step_limit {
+                if self.steps >= limit {
+                    return Err(IntcodeError::StepLimitExceeded {
+                        ip,
+                        instruction: code,
+                        limit,
+                    });
+                }
+            }
+
+            match decoded.op {
+                InstructionType::ADD
+                | InstructionType::MULTIPLY
+                | InstructionType::LESS_THAN
+                | InstructionType::EQUALS => {
+                    let a = self.load(decoded.modes[0], ip + 1)?;
+                    let b = self.load(decoded.modes[1], ip + 2)?;
+                    let c = self.target_of(decoded.modes[2], ip + 3)?;
+
+                    let value = match decoded.op {
+                        InstructionType::ADD => a.checked_add(b).ok_or_else(|| self.overflow())?,
+                        InstructionType::MULTIPLY => {
+                            a.checked_mul(b).ok_or_else(|| self.overflow())?
+                        }
+                        InstructionType::LESS_THAN => (a < b) as i64,
+                        _ => (a == b) as i64,
+                    };
+                    self.store(c, value);
+                    self.memory_pos += 4;
+                }
+                InstructionType::JUMP_IF_TRUE | InstructionType::JUMP_IF_FALSE => {
+                    let a = self.load(decoded.modes[0], ip + 1)?;
+                    let b = self.load(decoded.modes[1], ip + 2)?;
+
+                    if (a != 0) == (decoded.op == InstructionType::JUMP_IF_TRUE) {
+                        self.memory_pos = self.target(b)?;
+                    } else {
+                        self.memory_pos += 3;
+                    }
+                }
+                InstructionType::READ => {
+                    let addr = self.target_of(decoded.modes[0], ip + 1)?;
+                    let value = match self.next_input() {
+                        Some(value) => value,
+                        None => return Ok(Status::NeedsInput),
+                    };
+
+                    self.store(addr, value);
+                    self.input_pos += 1;
+                    self.memory_pos += 2;
+                }
+                InstructionType::WRITE => {
+                    let value = self.load(decoded.modes[0], ip + 1)?;
+
+                    if !self.pause_on_output {
+                        self.emit(ip, value)?;
+                    }
+                    self.output_pos += 1;
+                    self.memory_pos += 2;
+                    self.steps += 1;
+
+                    if self.pause_on_output {
+                        return Ok(Status::Output(value));
+                    }
+                    continue;
+                }
+                InstructionType::ADJUST_RELATIVE_BASE => {
+                    self.relative_base = self.relative(self.load(decoded.modes[0], ip + 1)?)?;
+                    self.memory_pos += 2;
+                }
+                InstructionType::HALT => {
+                    self.steps += 1;
+                    return Ok(Status::Halted);
+                }
+            }
+
+            self.steps += 1;
+        }
+    }
+
+    // value of the parameter stored at param
+    #[inline]
+    fn load(&self, mode: InstructionArgumentMode, param: usize) -> Result<i64, IntcodeError> {
+        let value = self.memory[param];
+
+        match mode {
+            InstructionArgumentMode::IMMEDIATE => Ok(value),
+            InstructionArgumentMode::POSITION => Ok(self.memory[self.target(value)?]),
+            InstructionArgumentMode::RELATIVE => {
+                Ok(self.memory[self.target(self.relative(value)?)?])
+            }
+        }
+    }
+
+    // address the parameter stored at param writes to, never immediate once decoded
+    #[inline]
+    fn target_of(
+        &self,
+        mode: InstructionArgumentMode,
+        param: usize,
+    ) -> Result<usize, IntcodeError> {
+        let value = self.memory[param];
+
+        match mode {
+            InstructionArgumentMode::RELATIVE => self.target(self.relative(value)?),
+            _ => self.target(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::computer::fast::Decoded;
+    use crate::computer::{Engine, InstructionArgumentMode, InstructionType, ProgramState, Status};
+    use crate::error::IntcodeError;
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            Decoded::new(21107),
+            Some(Decoded {
+                op: InstructionType::LESS_THAN,
+                modes: [
+                    InstructionArgumentMode::IMMEDIATE,
+                    InstructionArgumentMode::IMMEDIATE,
+                    InstructionArgumentMode::RELATIVE,
+                ],
+            })
+        );
+        assert_eq!(Decoded::new(11101), None);
+        assert_eq!(Decoded::new(103), None);
+        assert_eq!(Decoded::new(42), None);
+        assert_eq!(Decoded::new(301), None);
+    }
+
+    fn run(program: &[i64], engine: Engine, input: Vec<i64>) -> (Vec<i64>, Vec<i64>, u64) {
+        let mut output = vec![];
+        let mut prog = ProgramState::new(program, VecDeque::from(input), &mut output);
+        prog.set_engine(engine);
+
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        let (memory, steps) = (prog.memory(), prog.steps());
+        drop(prog);
+
+        (output, memory, steps)
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let program = vec![
+            2, 20, 21, 22, // 0: [22] = [20] * [21], an ADD from the second pass on
+            4, 22, // 4: OUT [22]
+            1101, 0, 1, 0, // 6: [0] = 1
+            1001, 23, -1, 23, // 10: [23] -= 1
+            1005, 23, 0, // 14: JT [23], #0
+            99, 0, 0, // 17: HLT
+            3, 4, 0, 2, // 20: operands, result and pass counter
+        ];
+
+        let (output, _, _) = run(&program, Engine::Fast, vec![]);
+        assert_eq!(output, vec![12, 7]);
+    }
+
+    fn fault(program: &[i64], engine: Engine) -> Result<Status, IntcodeError> {
+        let mut prog = ProgramState::new(program, VecDeque::new(), vec![]);
+        prog.set_engine(engine);
+        prog.run()
+    }
+
+    #[test]
+    fn engines_agree() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(run(&quine, Engine::Fast, vec![]).0, quine);
+
+        // compares the input with 8: 999 below, 1000 equal, 1001 above
+        let compare = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for input in [7, 8, 9] {
+            assert_eq!(
+                run(&compare, Engine::Fast, vec![input]),
+                run(&compare, Engine::Reference, vec![input])
+            );
+        }
+
+        let faults = [
+            // JF not taken, its target is still resolved
+            vec![6, 3, -1, 99],
+            vec![5, 0, 5, 99, 0, -1],
+            vec![1102, i64::MAX, 2, 5, 99, 0],
+            vec![1101, 1, 1, 5, 42],
+            vec![11101, 1, 1, 3, 99],
+            vec![109, -5, 204, 2, 99],
+            vec![109, i64::MAX, 109, 1, 99],
+            vec![109, i64::MAX, 204, 1, 99],
+            vec![3, 0, 99],
+        ];
+
+        for program in faults {
+            assert_eq!(
+                fault(&program, Engine::Fast),
+                fault(&program, Engine::Reference)
+            );
+        }
+        assert_eq!(
+            fault(&[6, 3, -1, 99], Engine::Fast),
+            Err(IntcodeError::NegativeAddress {
+                ip: 0,
+                instruction: 6,
+                address: -1,
+            })
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use anyhow::{anyhow, Result};
+
+use crate::computer::{ProgramState, Status};
+use crate::disassembler::{decode, Line};
+use crate::session::Session;
+
+pub const HELP: &str = "\
+step [n]             execute n instructions (default 1)
+continue             run until a breakpoint, watchpoint, input wait or halt
+break <addr>         stop before executing the instruction at addr
+delete <addr>        remove the breakpoint at addr
+watch <addr>         stop after the value at addr changes
+unwatch <addr>       remove the watchpoint at addr
+regs                 show ip, relative base and input/output cursors
+mem <start> [end]    dump memory from start up to, not including, end
+poke <addr> <value>  store value at addr
+input <value>...     queue input values
+list [addr] [n]      disassemble n instructions from addr (default ip, 10)
+save <file>          save the session, as JSON if file ends in .json
+help                 show this help
+quit                 leave the debugger";
+
+// most cells `mem` dumps or instructions `list` disassembles in one reply
+const MAX_LISTED: usize = 10_000;
+
+// Interactive front end over a ProgramState: every command returns the text to show the user.
+pub struct Debugger {
+    state: ProgramState<'static>,
+    breakpoints: BTreeSet<usize>,
+    // watched address and the value it had when last checked
+    watchpoints: BTreeMap<usize, i64>,
+    halted: bool,
+    // every value the program printed, saved along with the machine
+    output: Vec<i64>,
+}
+
+impl Debugger {
+    pub fn new(program: &[i64]) -> Self {
+        Self::with_state(ProgramState::new(program, VecDeque::new(), vec![]), vec![])
+    }
+
+    // continues a session written by the save command
+    pub fn from_session(session: &Session) -> Self {
+        let state = ProgramState::from_snapshot(&session.machine, VecDeque::new(), vec![]);
+        Self::with_state(state, session.output.clone())
+    }
+
+    fn with_state(mut state: ProgramState<'static>, output: Vec<i64>) -> Self {
+        state.set_pause_on_output(true);
+
+        Self {
+            state,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            halted: false,
+            output,
+        }
+    }
+
+    pub fn session(&self) -> Session {
+        Session {
+            machine: self.state.snapshot(),
+            output: self.output.clone(),
+        }
+    }
+
+    pub fn execute(&mut self, command: &str) -> Result<String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+
+        match name {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(n) => parse(n)?,
+                    None => 1,
+                };
+                self.resume(Some(count))
+            }
+            "c" | "continue" => self.resume(None),
+            "b" | "break" => {
+                let addr = parse(arg(&args, 0)?)?;
+                self.breakpoints.insert(addr);
+                Ok(format!("breakpoint at {}", addr))
+            }
+            "delete" => {
+                let addr = parse(arg(&args, 0)?)?;
+                match self.breakpoints.remove(&addr) {
+                    true => Ok(format!("deleted breakpoint at {}", addr)),
+                    false => Err(anyhow!("no breakpoint at {}", addr)),
+                }
+            }
+            "w" | "watch" => {
+                let addr = parse(arg(&args, 0)?)?;
+                let value = self.state.peek(addr);
+                self.watchpoints.insert(addr, value);
+                Ok(format!("watching [{}] = {}", addr, value))
+            }
+            "unwatch" => {
+                let addr = parse(arg(&args, 0)?)?;
+                match self.watchpoints.remove(&addr) {
+                    Some(_) => Ok(format!("no longer watching [{}]", addr)),
+                    None => Err(anyhow!("no watchpoint at {}", addr)),
+                }
+            }
+            "r" | "regs" => Ok(self.registers()),
+            "x" | "mem" => {
+                let start: usize = parse(arg(&args, 0)?)?;
+                let count = match args.get(1) {
+                    Some(end) => parse::<usize>(end)?.saturating_sub(start),
+                    None => 1,
+                };
+                check_count(count)?;
+                Ok(self.dump(start, count))
+            }
+            "poke" => {
+                let addr = parse(arg(&args, 0)?)?;
+                let value = parse(arg(&args, 1)?)?;
+                self.state.poke(addr, value);
+                Ok(format!("[{}] = {}", addr, value))
+            }
+            "i" | "input" => {
+                if args.is_empty() {
+                    return Err(anyhow!("input needs at least one value"));
+                }
+                for value in &args {
+                    let value = parse(value)?;
+                    self.state.feed(value);
+                }
+                Ok(format!("pending input: {:?}", self.state.pending_input()))
+            }
+            "l" | "list" => {
+                let addr = match args.first() {
+                    Some(addr) => parse(addr)?,
+                    None => self.state.ip(),
+                };
+                let count = match args.get(1) {
+                    Some(count) => parse(count)?,
+                    None => 10,
+                };
+                check_count(count)?;
+                Ok(self.list(addr, count))
+            }
+            "save" => {
+                let path = arg(&args, 0)?;
+                self.session().save(path)?;
+                Ok(format!("saved to {}", path))
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            _ => Err(anyhow!("unknown command {:?}, try help", name)),
+        }
+    }
+
+    // executes up to `limit` instructions, or until something stops the machine when None
+    fn resume(&mut self, limit: Option<usize>) -> Result<String> {
+        if self.halted {
+            return Err(anyhow!("program has halted"));
+        }
+
+        let mut report = vec![];
+        let mut executed = 0;
+
+        loop {
+            if limit.is_some_and(|limit| executed >= limit) {
+                break;
+            }
+            if limit.is_none() && executed > 0 && self.breakpoints.contains(&self.state.ip()) {
+                report.push(format!("breakpoint at {}", self.state.ip()));
+                break;
+            }
+
+            match self.state.step()? {
+                Some(Status::Output(value)) => {
+                    self.output.push(value);
+                    report.push(format!("output: {}", value));
+                }
+                Some(Status::NeedsInput) => {
+                    report.push("waiting for input".to_string());
+                    break;
+                }
+                Some(Status::Halted) => {
+                    self.halted = true;
+                    report.push("halted".to_string());
+                    break;
+                }
+                None => {}
+            }
+            executed += 1;
+
+            if self.watchpoints_changed(&mut report) {
+                break;
+            }
+        }
+
+        report.push(self.current());
+        Ok(report.join("\n"))
+    }
+
+    fn watchpoints_changed(&mut self, report: &mut Vec<String>) -> bool {
+        let mut changed = false;
+
+        for (addr, old) in self.watchpoints.iter_mut() {
+            let new = self.state.peek(*addr);
+            if new != *old {
+                report.push(format!("watchpoint [{}]: {} -> {}", addr, old, new));
+                *old = new;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn registers(&self) -> String {
+        format!(
+            "ip: {}\nrelative base: {}\ninput read: {}\noutput written: {}\npending input: {:?}",
+            self.state.ip(),
+            self.state.relative_base(),
+            self.state.input_pos(),
+            self.state.output_pos(),
+            self.state.pending_input()
+        )
+    }
+
+    fn dump(&self, start: usize, count: usize) -> String {
+        (0..count)
+            .map_while(|n| start.checked_add(n))
+            .collect::<Vec<usize>>()
+            .chunks(8)
+            .map(|row| {
+                let values: Vec<String> = row
+                    .iter()
+                    .map(|&addr| format!("{:>8}", self.state.peek(addr)))
+                    .collect();
+                format!("{:>5}: {}", row[0], values.join(" "))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn instruction_at(&self, addr: usize) -> Line {
+        // cells past the last address read as zero
+        let cells: Vec<i64> = (0..4)
+            .map(|n| addr.checked_add(n).map_or(0, |a| self.state.peek(a)))
+            .collect();
+
+        decode(&cells, addr).unwrap_or(Line::Data {
+            address: addr,
+            values: vec![cells[0]],
+        })
+    }
+
+    fn list(&self, mut addr: usize, count: usize) -> String {
+        let mut lines = vec![];
+
+        for _ in 0..count {
+            let marker = if self.breakpoints.contains(&addr) {
+                "*"
+            } else {
+                " "
+            };
+            let line = self.instruction_at(addr);
+
+            let len = match &line {
+                Line::Instruction { operands, .. } => 1 + operands.len(),
+                Line::Data { .. } => 1,
+            };
+            lines.push(format!("{} {}", marker, line));
+
+            match addr.checked_add(len) {
+                Some(next) => addr = next,
+                None => break,
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    fn current(&self) -> String {
+        format!("=> {}", self.instruction_at(self.state.ip()))
+    }
+}
+
+fn arg<'s>(args: &[&'s str], n: usize) -> Result<&'s str> {
+    args.get(n)
+        .copied()
+        .ok_or_else(|| anyhow!("missing argument {}, try help", n + 1))
+}
+
+// keeps `mem` and `list` from building replies larger than anyone reads
+fn check_count(count: usize) -> Result<()> {
+    if count > MAX_LISTED {
+        return Err(anyhow!(
+            "{} requested, at most {} are shown at once",
+            count,
+            MAX_LISTED
+        ));
+    }
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid number {:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::Debugger;
+
+    // output the sum of two inputs
+    fn adder() -> Vec<i64> {
+        vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]
+    }
+
+    #[test]
+    fn step_and_registers() {
+        let mut debugger = Debugger::new(&adder());
+
+        assert_eq!(
+            debugger.execute("input 2 40").unwrap(),
+            "pending input: [2, 40]"
+        );
+        assert_eq!(
+            debugger.execute("step 2").unwrap(),
+            "=>     4: ADD [11], [12], [13]"
+        );
+        assert_eq!(
+            debugger.execute("regs").unwrap(),
+            "ip: 4\nrelative base: 0\ninput read: 2\noutput written: 0\npending input: []"
+        );
+        assert_eq!(
+            debugger.execute("mem 10 14").unwrap(),
+            "   10:       99        2       40        0"
+        );
+        assert_eq!(
+            debugger.execute("continue").unwrap(),
+            "output: 42\nhalted\n=>    10: HLT"
+        );
+        assert!(debugger.execute("step").is_err());
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        let mut debugger = Debugger::new(&adder());
+
+        debugger.execute("break 8").unwrap();
+        debugger.execute("watch 12").unwrap();
+        debugger.execute("poke 11 5").unwrap();
+
+        assert_eq!(
+            debugger.execute("c").unwrap(),
+            "waiting for input\n=>     0: IN [11]"
+        );
+
+        debugger.execute("i 1 2").unwrap();
+        assert_eq!(
+            debugger.execute("c").unwrap(),
+            "watchpoint [12]: 0 -> 2\n=>     4: ADD [11], [12], [13]"
+        );
+        assert_eq!(
+            debugger.execute("c").unwrap(),
+            "breakpoint at 8\n=>     8: OUT [13]"
+        );
+        assert_eq!(
+            debugger.execute("list 8 2").unwrap(),
+            "*     8: OUT [13]\n     10: HLT"
+        );
+    }
+
+    #[test]
+    fn bad_commands() {
+        let mut debugger = Debugger::new(&adder());
+
+        assert!(debugger.execute("jump 4").is_err());
+        assert!(debugger.execute("break").is_err());
+        assert!(debugger.execute("poke x 1").is_err());
+        assert!(debugger.execute("delete 3").is_err());
+        assert!(debugger.execute("save").is_err());
+        assert!(debugger.execute("mem 0 100000000000").is_err());
+        assert!(debugger.execute("list 0 100000000000").is_err());
+    }
+
+    #[test]
+    fn last_address() {
+        let mut debugger = Debugger::new(&adder());
+
+        assert_eq!(
+            debugger.execute("mem 18446744073709551615").unwrap(),
+            "18446744073709551615:        0"
+        );
+        assert_eq!(
+            debugger.execute("list 18446744073709551615 3").unwrap(),
+            "  18446744073709551615: DATA 0"
+        );
+    }
+
+    #[test]
+    fn resume_session() {
+        let mut debugger = Debugger::new(&adder());
+        debugger.execute("input 2").unwrap();
+        debugger.execute("continue").unwrap();
+
+        let session = debugger.session();
+        assert_eq!(session.machine, debugger.state.snapshot());
+
+        let mut resumed = Debugger::from_session(&session);
+        assert_eq!(
+            resumed.execute("regs").unwrap(),
+            debugger.execute("regs").unwrap()
+        );
+
+        resumed.execute("input 40").unwrap();
+        assert_eq!(
+            resumed.execute("continue").unwrap(),
+            "output: 42\nhalted\n=>    10: HLT"
+        );
+        assert_eq!(resumed.session().output, vec![42]);
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::computer::{parse_instruction, InstructionArgumentMode, InstructionType};
+
+// One entry of a listing: a decoded instruction or a run of cells that do not decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        mnemonic: &'static str,
+        operands: Vec<String>,
+    },
+    Data {
+        address: usize,
+        values: Vec<i64>,
+    },
+}
+
+impl Line {
+    // the line without its address, e.g. "MUL [4], #3, [4]"
+    pub fn body(&self) -> String {
+        match self {
+            Line::Instruction {
+                mnemonic, operands, ..
+            } => {
+                if operands.is_empty() {
+                    mnemonic.to_string()
+                } else {
+                    format!("{} {}", mnemonic, operands.join(", "))
+                }
+            }
+            Line::Data { values, .. } => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                format!("DATA {}", values.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => address,
+        };
+
+        write!(f, "{:>5}: {}", address, self.body())
+    }
+}
+
+pub(crate) fn mnemonic(opcode: &InstructionType) -> &'static str {
+    match opcode {
+        InstructionType::ADD => "ADD",
+        InstructionType::MULTIPLY => "MUL",
+        InstructionType::READ => "IN",
+        InstructionType::WRITE => "OUT",
+        InstructionType::JUMP_IF_TRUE => "JT",
+        InstructionType::JUMP_IF_FALSE => "JF",
+        InstructionType::LESS_THAN => "LT",
+        InstructionType::EQUALS => "EQ",
+        InstructionType::ADJUST_RELATIVE_BASE => "ARB",
+        InstructionType::HALT => "HLT",
+    }
+}
+
+// [225] for position, #7 for immediate, rb+3 for relative parameters
+pub(crate) fn operand(mode: &InstructionArgumentMode, param: i64) -> String {
+    match mode {
+        InstructionArgumentMode::POSITION => format!("[{}]", param),
+        InstructionArgumentMode::IMMEDIATE => format!("#{}", param),
+        InstructionArgumentMode::RELATIVE if param < 0 => format!("rb{}", param),
+        InstructionArgumentMode::RELATIVE => format!("rb+{}", param),
+    }
+}
+
+// Decodes the instruction at the start of `cells`, which begin at `address`. None if the cell is
+// not a valid instruction or its parameters would run past the end of `cells`.
+pub fn decode(cells: &[i64], address: usize) -> Option<Line> {
+    let instruction = parse_instruction(address, *cells.first()?).ok()?;
+    let count = instruction.opcode.arg_count();
+    if count >= cells.len() {
+        return None;
+    }
+
+    let operands = (0..count)
+        .map(|n| operand(&instruction.arg_modes[n], cells[1 + n]))
+        .collect();
+
+    Some(Line::Instruction {
+        address,
+        mnemonic: mnemonic(&instruction.opcode),
+        operands,
+    })
+}
+
+// Decodes the memory image from address 0 on. Cells that are not a valid instruction, or whose
+// parameters would run past the end of the image, are collected into Data lines.
+pub fn disassemble(memory: &[i64]) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut address = 0;
+
+    while address < memory.len() {
+        match decode(&memory[address..], address) {
+            Some(line) => {
+                if let Line::Instruction { operands, .. } = &line {
+                    address += 1 + operands.len();
+                }
+                lines.push(line);
+            }
+            None => {
+                match lines.last_mut() {
+                    Some(Line::Data { values, .. }) => values.push(memory[address]),
+                    _ => lines.push(Line::Data {
+                        address,
+                        values: vec![memory[address]],
+                    }),
+                }
+                address += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+// the whole listing, one line per instruction or data run
+pub fn listing(memory: &[i64]) -> String {
+    disassemble(memory)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disassembler::{disassemble, listing, Line};
+
+    #[test]
+    fn operand_modes() {
+        let memory = vec![1002, 4, 3, 4, 21101, 7, -2, 1, 204, -3, 99];
+
+        assert_eq!(
+            listing(&memory),
+            "    0: MUL [4], #3, [4]\n    4: ADD #7, #-2, rb+1\n    8: OUT rb-3\n   10: HLT\n"
+        );
+    }
+
+    #[test]
+    fn data_regions() {
+        // jump over three cells that do not decode, then a truncated ADD at the end
+        let memory = vec![1105, 1, 6, 0, 55, 301, 109, 4, 99, 1, 2];
+
+        assert_eq!(
+            disassemble(&memory),
+            vec![
+                Line::Instruction {
+                    address: 0,
+                    mnemonic: "JT",
+                    operands: vec!["#1".to_string(), "#6".to_string()],
+                },
+                Line::Data {
+                    address: 3,
+                    values: vec![0, 55, 301],
+                },
+                Line::Instruction {
+                    address: 6,
+                    mnemonic: "ARB",
+                    operands: vec!["#4".to_string()],
+                },
+                Line::Instruction {
+                    address: 8,
+                    mnemonic: "HLT",
+                    operands: vec![],
+                },
+                Line::Data {
+                    address: 9,
+                    values: vec![1, 2],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+
+// A fault raised by a running machine. Every variant records the instruction pointer and the raw
+// opcode cell of the instruction that failed, e.g. 1002.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    InvalidOpcode {
+        ip: usize,
+        instruction: i64,
+    },
+    // a mode digit other than 0, 1 or 2, or immediate mode on a parameter written to
+    InvalidMode {
+        ip: usize,
+        instruction: i64,
+        parameter: usize,
+        mode: i64,
+    },
+    // a parameter, relative base offset or jump target resolved below address 0
+    NegativeAddress {
+        ip: usize,
+        instruction: i64,
+        address: i64,
+    },
+    // ADD, MUL or the relative base produced a value that does not fit in 64 bits
+    ArithmeticOverflow {
+        ip: usize,
+        instruction: i64,
+    },
+    // READ found no input where the caller expected the program to run to completion
+    InputExhausted {
+        ip: usize,
+        instruction: i64,
+    },
+    // the output sink refused a value
+    OutputOverflow {
+        ip: usize,
+        instruction: i64,
+        value: i64,
+    },
+    StepLimitExceeded {
+        ip: usize,
+        instruction: i64,
+        limit: u64,
+    },
+    // the machine is about to execute from a state it was in before, so it never halts
+    LoopDetected {
+        ip: usize,
+        instruction: i64,
+        // step at which the state was first seen, the loop is `step - first_seen` instructions long
+        first_seen: u64,
+        step: u64,
+    },
+    // the installed tracer failed to record the instruction
+    TraceFailed {
+        ip: usize,
+        instruction: i64,
+        reason: String,
+    },
+}
+
+impl IntcodeError {
+    pub fn ip(&self) -> usize {
+        match self {
+            IntcodeError::InvalidOpcode { ip, .. }
+            | IntcodeError::InvalidMode { ip, .. }
+            | IntcodeError::NegativeAddress { ip, .. }
+            | IntcodeError::ArithmeticOverflow { ip, .. }
+            | IntcodeError::InputExhausted { ip, .. }
+            | IntcodeError::OutputOverflow { ip, .. }
+            | IntcodeError::StepLimitExceeded { ip, .. }
+            | IntcodeError::LoopDetected { ip, .. }
+            | IntcodeError::TraceFailed { ip, .. } => *ip,
+        }
+    }
+
+    pub fn instruction(&self) -> i64 {
+        match self {
+            IntcodeError::InvalidOpcode { instruction, .. }
+            | IntcodeError::InvalidMode { instruction, .. }
+            | IntcodeError::NegativeAddress { instruction, .. }
+            | IntcodeError::ArithmeticOverflow { instruction, .. }
+            | IntcodeError::InputExhausted { instruction, .. }
+            | IntcodeError::OutputOverflow { instruction, .. }
+            | IntcodeError::StepLimitExceeded { instruction, .. }
+            | IntcodeError::LoopDetected { instruction, .. }
+            | IntcodeError::TraceFailed { instruction, .. } => *instruction,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpcode { .. } => write!(f, "invalid opcode"),
+            IntcodeError::InvalidMode {
+                parameter, mode, ..
+            } => write!(f, "invalid mode {} for parameter {}", mode, parameter + 1),
+            IntcodeError::NegativeAddress { address, .. } => {
+                write!(f, "negative address {}", address)
+            }
+            IntcodeError::ArithmeticOverflow { .. } => write!(f, "arithmetic overflow"),
+            IntcodeError::InputExhausted { .. } => write!(f, "input exhausted"),
+            IntcodeError::OutputOverflow { value, .. } => {
+                write!(f, "output sink rejected value {}", value)
+            }
+            IntcodeError::StepLimitExceeded { limit, .. } => {
+                write!(f, "step limit of {} exceeded", limit)
+            }
+            IntcodeError::LoopDetected {
+                first_seen, step, ..
+            } => write!(
+                f,
+                "state at step {} repeats step {}, loop of {} steps",
+                step,
+                first_seen,
+                step - first_seen
+            ),
+            IntcodeError::TraceFailed { reason, .. } => write!(f, "tracer failed: {}", reason),
+        }?;
+        write!(f, " at {} (instruction {})", self.ip(), self.instruction())
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+use anyhow::{anyhow, Result};
+
+// Where READ takes its values from. None means no more input is available.
+pub trait Input {
+    fn read(&mut self) -> Option<i64>;
+}
+
+// Where WRITE puts its values. Sinks that cannot accept a value return an error.
+pub trait Output {
+    fn write(&mut self, value: i64) -> Result<()>;
+}
+
+impl Input for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+// blocks until a value arrives, runs out once every sender is dropped
+impl Input for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+impl<T: Input + ?Sized> Input for &mut T {
+    fn read(&mut self) -> Option<i64> {
+        (**self).read()
+    }
+}
+
+impl Output for Vec<i64> {
+    fn write(&mut self, value: i64) -> Result<()> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn write(&mut self, value: i64) -> Result<()> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl Output for Sender<i64> {
+    fn write(&mut self, value: i64) -> Result<()> {
+        self.send(value)
+            .map_err(|_| anyhow!("output channel closed, value {} dropped", value))
+    }
+}
+
+impl<T: Output + ?Sized> Output for &mut T {
+    fn write(&mut self, value: i64) -> Result<()> {
+        (**self).write(value)
+    }
+}
+
+pub struct IterInput<I>(I);
+
+impl<I: Iterator<Item = i64>> Input for IterInput<I> {
+    fn read(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+// input taken from any iterator, e.g. input_iter(vec![1, 2]) or input_iter(0..10)
+pub fn input_iter<I: IntoIterator<Item = i64>>(iter: I) -> IterInput<I::IntoIter> {
+    IterInput(iter.into_iter())
+}
+
+pub struct FnInput<F>(F);
+
+impl<F: FnMut() -> Option<i64>> Input for FnInput<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+// input computed on demand, each READ calls the closure
+pub fn input_fn<F: FnMut() -> Option<i64>>(f: F) -> FnInput<F> {
+    FnInput(f)
+}
+
+pub struct FnOutput<F>(F);
+
+impl<F: FnMut(i64) -> Result<()>> Output for FnOutput<F> {
+    fn write(&mut self, value: i64) -> Result<()> {
+        (self.0)(value)
+    }
+}
+
+// output handed to a closure as soon as it is produced
+pub fn output_fn<F: FnMut(i64) -> Result<()>>(f: F) -> FnOutput<F> {
+    FnOutput(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::sync::mpsc;
+
+    use crate::computer::{ProgramState, Status};
+    use crate::io::{input_fn, input_iter, output_fn};
+
+    // read a value, output it doubled, repeat
+    fn doubler() -> Vec<i64> {
+        vec![3, 100, 1002, 100, 2, 100, 4, 100, 1105, 1, 0]
+    }
+
+    #[test]
+    fn iterator_input() {
+        let memory = doubler();
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, input_iter(1..=3), &mut output);
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        drop(prog);
+        assert_eq!(output, vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn channels() {
+        let (in_tx, in_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+
+        for n in [5, 6] {
+            in_tx.send(n).unwrap();
+        }
+        drop(in_tx);
+
+        let memory = doubler();
+        let mut prog = ProgramState::new(&memory, in_rx, out_tx);
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        drop(prog);
+        assert_eq!(out_rx.iter().collect::<Vec<_>>(), vec![10, 12]);
+    }
+
+    #[test]
+    fn input_from_previous_output() {
+        let queue = RefCell::new(VecDeque::from(vec![1]));
+        let mut seen = vec![];
+
+        let memory = doubler();
+        let mut prog = ProgramState::new(
+            &memory,
+            input_fn(|| queue.borrow_mut().pop_front()),
+            output_fn(|value| {
+                seen.push(value);
+                if value < 1000 {
+                    queue.borrow_mut().push_back(value + 1);
+                }
+                Ok(())
+            }),
+        );
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        drop(prog);
+        assert_eq!(seen, vec![2, 6, 14, 30, 62, 126, 254, 510, 1022]);
+    }
+}
@@ -0,0 +1,18 @@
+pub mod ascii;
+pub mod assembler;
+pub mod cfg;
+pub mod computer;
+pub mod debugger;
+pub mod disassembler;
+pub mod error;
+pub mod io;
+pub mod loader;
+mod memory;
+pub mod network;
+pub mod pipeline;
+pub mod profile;
+pub mod search;
+pub mod selfmod;
+pub mod session;
+pub mod symbolic;
+pub mod trace;
@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+// first bytes of a binary image, text programs never start with it
+const MAGIC: &[u8] = b"ICPROG1\n";
+
+// Where a program comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source<'a> {
+    Path(&'a Path),
+    Stdin,
+    // built into the binary, e.g. include_bytes!("../input.txt")
+    Embedded(&'a [u8]),
+}
+
+impl<'a> Source<'a> {
+    // a command line argument: `-` is stdin, anything else a path
+    pub fn from_arg(arg: &'a str) -> Self {
+        match arg {
+            "-" => Source::Stdin,
+            path => Source::Path(Path::new(path)),
+        }
+    }
+}
+
+// A token in a text program that is not a number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // byte offset of the token in the text, after leading whitespace
+    pub offset: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "missing number at offset {}", self.offset)
+        } else {
+            write!(
+                f,
+                "malformed number {:?} at offset {}",
+                self.token, self.offset
+            )
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// How the bytes of a program are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // text, or a binary image behind MAGIC, told apart by the leading bytes
+    Detect,
+    // little-endian cells without a header, as other tools write them
+    RawBinary,
+}
+
+impl Format {
+    // `.bin` files are raw binary images, everything else is detected
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("bin") => Format::RawBinary,
+            _ => Format::Detect,
+        }
+    }
+}
+
+// Loads in the format `Format::from_path` picks for paths, detected for the other sources.
+pub fn load(source: Source) -> Result<Vec<i64>> {
+    let format = match source {
+        Source::Path(path) => Format::from_path(path),
+        Source::Stdin | Source::Embedded(_) => Format::Detect,
+    };
+    load_as(source, format)
+}
+
+pub fn load_as(source: Source, format: Format) -> Result<Vec<i64>> {
+    match source {
+        Source::Path(path) => {
+            let bytes =
+                fs::read(path).with_context(|| format!("reading program {}", path.display()))?;
+            parse_as(&bytes, format).with_context(|| format!("loading program {}", path.display()))
+        }
+        Source::Stdin => {
+            let mut bytes = vec![];
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .context("reading program from stdin")?;
+            parse_as(&bytes, format).context("loading program from stdin")
+        }
+        Source::Embedded(bytes) => parse_as(bytes, format).context("loading embedded program"),
+    }
+}
+
+pub fn parse_as(bytes: &[u8], format: Format) -> Result<Vec<i64>> {
+    match format {
+        Format::Detect => parse(bytes),
+        Format::RawBinary => parse_raw_binary(bytes),
+    }
+}
+
+// Either format with a leading marker: a binary image behind MAGIC, otherwise text.
+pub fn parse(bytes: &[u8]) -> Result<Vec<i64>> {
+    if bytes.starts_with(MAGIC) {
+        return parse_binary(bytes);
+    }
+
+    let text = std::str::from_utf8(bytes).context("text program is not UTF-8")?;
+    Ok(parse_text(text)?)
+}
+
+// Comma separated numbers; whitespace and newlines around them are ignored.
+pub fn parse_text(text: &str) -> Result<Vec<i64>, ParseError> {
+    let mut offset = 0;
+
+    text.split(',')
+        .map(|raw| {
+            let token = raw.trim();
+            let start = offset + (raw.len() - raw.trim_start().len());
+            offset += raw.len() + 1;
+
+            token.parse().map_err(|_| ParseError {
+                offset: start,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+// MAGIC, then the cells as in `parse_raw_binary`.
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<i64>> {
+    let cells = bytes
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| anyhow!("not a binary Intcode image"))?;
+    parse_raw_binary(cells)
+}
+
+// Eight bytes per cell, little-endian.
+pub fn parse_raw_binary(bytes: &[u8]) -> Result<Vec<i64>> {
+    if !bytes.len().is_multiple_of(8) {
+        return Err(anyhow!(
+            "binary image of {} bytes does not hold whole 8-byte cells",
+            bytes.len()
+        ));
+    }
+
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|cell| i64::from_le_bytes(cell.try_into().expect("8 bytes")))
+        .collect())
+}
+
+pub fn to_binary(program: &[i64]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(program.iter().flat_map(|cell| cell.to_le_bytes()));
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::loader::{
+        load, load_as, parse, parse_as, parse_raw_binary, parse_text, to_binary, Format,
+        ParseError, Source,
+    };
+
+    #[test]
+    fn text() {
+        assert_eq!(parse_text("1,0,0,3,99\n").unwrap(), vec![1, 0, 0, 3, 99]);
+        assert_eq!(
+            parse_text(" 1002, 4,\n3 ,\r\n4,-33 ").unwrap(),
+            vec![1002, 4, 3, 4, -33]
+        );
+    }
+
+    #[test]
+    fn malformed_tokens() {
+        let error = parse_text("1,2,\n  3x,4").unwrap_err();
+        assert_eq!(
+            error,
+            ParseError {
+                offset: 7,
+                token: "3x".to_string(),
+            }
+        );
+        assert_eq!(error.to_string(), "malformed number \"3x\" at offset 7");
+
+        assert_eq!(parse_text("1,,2").unwrap_err().offset, 2);
+        assert_eq!(
+            parse_text("99,\n").unwrap_err().to_string(),
+            "missing number at offset 4"
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let program = vec![1002, 4, 3, 4, -33, i64::MAX];
+
+        assert_eq!(to_binary(&program).len(), 8 + 48);
+        assert_eq!(parse(&to_binary(&program)).unwrap(), program);
+        assert!(parse(&to_binary(&program)[..55]).is_err());
+
+        // cells whose bytes are all digits are still read as binary
+        let digits = vec![0x3131313131313131];
+        assert_eq!(parse(&to_binary(&digits)).unwrap(), digits);
+        assert_eq!(parse(b"11111111").unwrap(), vec![11111111]);
+        assert!(parse(&[0xff, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn raw_binary() {
+        let image = [99, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(parse_raw_binary(&image).unwrap(), vec![99]);
+        assert_eq!(parse_as(&image, Format::RawBinary).unwrap(), vec![99]);
+        assert!(parse_as(&image, Format::Detect).is_err());
+        assert!(parse_raw_binary(&image[..7]).is_err());
+        assert_eq!(
+            load_as(Source::Embedded(&image), Format::RawBinary).unwrap(),
+            vec![99]
+        );
+
+        assert_eq!(Format::from_path(Path::new("day9.BIN")), Format::RawBinary);
+        assert_eq!(Format::from_path(Path::new("day9.txt")), Format::Detect);
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(Source::from_arg("-"), Source::Stdin);
+        assert_eq!(
+            load(Source::Embedded(b"3,0,4,0,99\n")).unwrap(),
+            vec![3, 0, 4, 0, 99]
+        );
+
+        let error = load(Source::from_arg("/nonexistent/program.txt")).unwrap_err();
+        assert_eq!(
+            format!("{}", error),
+            "reading program /nonexistent/program.txt"
+        );
+
+        let error = load(Source::Embedded(b"1,x")).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "loading embedded program: malformed number \"x\" at offset 2"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+// writes up to this many cells past the end of the loaded image extend the image itself,
+// anything further away goes to the sparse map so a single far write does not allocate gigabytes
+const DENSE_GROWTH_LIMIT: usize = 4096;
+
+pub(crate) const PAGE_SIZE: usize = 1024;
+
+type Page = [i64; PAGE_SIZE];
+
+static ZERO: i64 = 0;
+
+// Program memory: the loaded image plus zero-filled space beyond it, split into pages that clones
+// share until one of them writes to it, so cloning memory costs one pointer per page.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "Cells", into = "Cells")]
+pub(crate) struct Memory {
+    // pages from address 0 covering the image
+    dense: Vec<Arc<Page>>,
+    // pages far past the image by page number
+    sparse: HashMap<usize, Arc<Page>>,
+    // cells in the image: the loaded program and everything up to the last dense write
+    len: usize,
+}
+
+impl Memory {
+    pub(crate) fn new(image: &[i64]) -> Self {
+        let dense = image
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+
+        Self {
+            dense,
+            sparse: HashMap::new(),
+            len: image.len(),
+        }
+    }
+
+    // the image as a plain vector, cells written sparsely are not included
+    pub(crate) fn image(&self) -> Vec<i64> {
+        self.dense
+            .iter()
+            .flat_map(|page| page.iter().copied())
+            .take(self.len)
+            .collect()
+    }
+
+    // every cell that may be non-zero: the image and the non-zero cells past it in address order
+    pub(crate) fn cells(&self) -> (Vec<i64>, Vec<(usize, i64)>) {
+        let mut beyond: Vec<(usize, i64)> = self
+            .pages()
+            .flat_map(|(n, page)| {
+                page.iter()
+                    .enumerate()
+                    .map(move |(offset, &val)| (n * PAGE_SIZE + offset, val))
+            })
+            .filter(|&(addr, val)| addr >= self.len && val != 0)
+            .collect();
+        beyond.sort_unstable();
+
+        (self.image(), beyond)
+    }
+
+    fn pages(&self) -> impl Iterator<Item = (usize, &Arc<Page>)> {
+        self.dense
+            .iter()
+            .enumerate()
+            .chain(self.sparse.iter().map(|(&n, page)| (n, page)))
+    }
+
+    fn grow(&mut self, addr: usize) {
+        self.len = addr + 1;
+
+        while self.dense.len() * PAGE_SIZE < self.len {
+            // a page written sparsely before the image grew over it
+            let page = self
+                .sparse
+                .remove(&self.dense.len())
+                .unwrap_or_else(|| Arc::new([0; PAGE_SIZE]));
+            self.dense.push(page);
+        }
+    }
+}
+
+// Serialized form of Memory: the image and the non-zero cells past it, pages are not persisted.
+#[derive(Serialize, Deserialize)]
+struct Cells {
+    image: Vec<i64>,
+    beyond: Vec<(usize, i64)>,
+}
+
+impl From<Memory> for Cells {
+    fn from(memory: Memory) -> Self {
+        let (image, beyond) = memory.cells();
+        Cells { image, beyond }
+    }
+}
+
+impl From<Cells> for Memory {
+    fn from(cells: Cells) -> Self {
+        let mut memory = Memory::new(&cells.image);
+
+        // straight into the page holding the cell, writing through IndexMut could grow the image
+        for (addr, val) in cells.beyond {
+            let n = addr / PAGE_SIZE;
+            let page = match memory.dense.get_mut(n) {
+                Some(page) => page,
+                None => memory
+                    .sparse
+                    .entry(n)
+                    .or_insert_with(|| Arc::new([0; PAGE_SIZE])),
+            };
+            Arc::make_mut(page)[addr % PAGE_SIZE] = val;
+        }
+
+        memory
+    }
+}
+
+// equal contents, however the cells are laid out in pages
+impl PartialEq for Memory {
+    fn eq(&self, other: &Self) -> bool {
+        self.cells() == other.cells()
+    }
+}
+
+impl Eq for Memory {}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (dense, sparse) = self.cells();
+
+        f.debug_struct("Memory")
+            .field("dense", &dense)
+            .field("sparse", &sparse)
+            .finish()
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, addr: usize) -> &i64 {
+        let (n, offset) = (addr / PAGE_SIZE, addr % PAGE_SIZE);
+
+        match self.dense.get(n).or_else(|| self.sparse.get(&n)) {
+            Some(page) => &page[offset],
+            None => &ZERO,
+        }
+    }
+}
+
+impl IndexMut<usize> for Memory {
+    fn index_mut(&mut self, addr: usize) -> &mut i64 {
+        if addr >= self.len {
+            if addr - self.len >= DENSE_GROWTH_LIMIT {
+                let page = self
+                    .sparse
+                    .entry(addr / PAGE_SIZE)
+                    .or_insert_with(|| Arc::new([0; PAGE_SIZE]));
+                return &mut Arc::make_mut(page)[addr % PAGE_SIZE];
+            }
+            self.grow(addr);
+        }
+
+        &mut Arc::make_mut(&mut self.dense[addr / PAGE_SIZE])[addr % PAGE_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::memory::{Memory, DENSE_GROWTH_LIMIT, PAGE_SIZE};
+
+    #[test]
+    fn read_past_end() {
+        let memory = Memory::new(&[1, 2, 3]);
+
+        assert_eq!(memory[2], 3);
+        assert_eq!(memory[3], 0);
+        assert_eq!(memory[1_000_000_000], 0);
+        assert_eq!(memory.image(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn write_past_end() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+
+        memory[5] = 42;
+        memory[1_000_000_000] = 7;
+
+        assert_eq!(memory[1_000_000_000], 7);
+        assert_eq!(memory.image(), vec![1, 2, 3, 0, 0, 42]);
+        assert_eq!(memory.cells().1, vec![(1_000_000_000, 7)]);
+    }
+
+    #[test]
+    fn grow_over_sparse() {
+        let mut memory = Memory::new(&[]);
+
+        memory[DENSE_GROWTH_LIMIT + 1] = 9;
+        memory[DENSE_GROWTH_LIMIT - 1] = 1;
+        assert_eq!(memory.sparse.len(), 1);
+
+        memory[DENSE_GROWTH_LIMIT + PAGE_SIZE] = 11;
+        assert!(memory.sparse.is_empty());
+        assert_eq!(memory[DENSE_GROWTH_LIMIT + 1], 9);
+        assert_eq!(memory.image().len(), DENSE_GROWTH_LIMIT + PAGE_SIZE + 1);
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let image: Vec<i64> = (0..3 * PAGE_SIZE as i64).collect();
+        let original = Memory::new(&image);
+        let mut copy = original.clone();
+
+        copy[PAGE_SIZE] = -1;
+
+        assert_eq!(original[PAGE_SIZE], PAGE_SIZE as i64);
+        assert_eq!(copy[PAGE_SIZE], -1);
+        assert!(Arc::ptr_eq(&original.dense[0], &copy.dense[0]));
+        assert!(!Arc::ptr_eq(&original.dense[1], &copy.dense[1]));
+    }
+}
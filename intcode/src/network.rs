@@ -0,0 +1,440 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::computer::{ProgramState, Status};
+use crate::error::IntcodeError;
+use crate::io::{input_fn, output_fn};
+
+// packets sent to this address go to the NAT instead of a machine
+pub const NAT_ADDRESS: i64 = 255;
+
+// a machine reading this many times from an empty queue in a row counts as idle
+const IDLE_READS: usize = 2;
+
+// how long the threaded router waits for a packet before checking for idleness
+const ROUTER_POLL: Duration = Duration::from_millis(1);
+
+// instructions a threaded machine runs between checks for shutdown, so machines that never read
+// input still stop once the router is done
+const THREAD_SLICE: u64 = 10_000;
+
+// Every machine boots with its address as the first input, then reads packets as x, y pairs
+// (-1 when its queue is empty) and sends packets as address, x, y output triples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub address: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduling {
+    // one thread per machine, packets routed through channels
+    Threaded,
+    // machines take turns on the calling thread, results are reproducible
+    RoundRobin,
+}
+
+// What the NAT saw until it delivered the same y value to address 0 twice in a row.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NatReport {
+    pub first_packet: Packet,
+    pub repeated_y: i64,
+}
+
+// Keeps the last packet sent to NAT_ADDRESS and sends it to address 0 whenever the network is idle.
+#[derive(Debug, Default)]
+struct Nat {
+    first: Option<Packet>,
+    last: Option<Packet>,
+    last_delivered_y: Option<i64>,
+}
+
+impl Nat {
+    fn receive(&mut self, packet: Packet) {
+        if self.first.is_none() {
+            self.first = Some(packet);
+        }
+        self.last = Some(packet);
+    }
+
+    // packet to wake up address 0 with, or the report once its y value repeats
+    fn wake(&mut self) -> Result<std::result::Result<Packet, NatReport>> {
+        let (first, last) = match (self.first, self.last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(anyhow!("network is idle and NAT has no packet to send")),
+        };
+
+        if self.last_delivered_y == Some(last.y) {
+            return Ok(Err(NatReport {
+                first_packet: first,
+                repeated_y: last.y,
+            }));
+        }
+        self.last_delivered_y = Some(last.y);
+
+        Ok(Ok(Packet {
+            address: 0,
+            x: last.x,
+            y: last.y,
+        }))
+    }
+}
+
+// Boots `size` copies of `program` with addresses 0..size and routes their packets until the NAT
+// delivers the same y value twice in a row.
+pub fn run_network(program: &[i64], size: usize, scheduling: Scheduling) -> Result<NatReport> {
+    if size == 0 {
+        return Err(anyhow!("network needs at least one machine"));
+    }
+
+    match scheduling {
+        Scheduling::Threaded => run_threaded(program, size),
+        Scheduling::RoundRobin => run_round_robin(program, size),
+    }
+}
+
+fn target(packet: &Packet, size: usize) -> Result<Option<usize>> {
+    if packet.address == NAT_ADDRESS {
+        return Ok(None);
+    }
+
+    match usize::try_from(packet.address) {
+        Ok(address) if address < size => Ok(Some(address)),
+        _ => Err(anyhow!("packet sent to unknown address: {:?}", packet)),
+    }
+}
+
+fn run_round_robin(program: &[i64], size: usize) -> Result<NatReport> {
+    let mut machines: Vec<ProgramState> = (0..size)
+        .map(|address| {
+            let mut machine = ProgramState::new(program, VecDeque::new(), vec![]);
+            machine.set_pause_on_output(true);
+            machine.feed(address as i64);
+            machine
+        })
+        .collect();
+
+    let mut queues: Vec<VecDeque<i64>> = vec![VecDeque::new(); size];
+    let mut partial: Vec<Vec<i64>> = vec![vec![]; size];
+    let mut nat = Nat::default();
+
+    loop {
+        let mut active = false;
+
+        for i in 0..size {
+            if queues[i].is_empty() {
+                machines[i].feed(-1);
+            } else {
+                active = true;
+                for value in queues[i].drain(..) {
+                    machines[i].feed(value);
+                }
+            }
+
+            // until the machine waits for more input or halts
+            while let Status::Output(value) = machines[i].run()? {
+                active = true;
+                partial[i].push(value);
+
+                if partial[i].len() == 3 {
+                    let packet = Packet {
+                        address: partial[i][0],
+                        x: partial[i][1],
+                        y: partial[i][2],
+                    };
+                    partial[i].clear();
+
+                    match target(&packet, size)? {
+                        Some(address) => queues[address].extend([packet.x, packet.y]),
+                        None => nat.receive(packet),
+                    }
+                }
+            }
+        }
+
+        if !active {
+            match nat.wake()? {
+                Ok(packet) => queues[0].extend([packet.x, packet.y]),
+                Err(report) => return Ok(report),
+            }
+        }
+    }
+}
+
+// What machine threads tell the threaded router.
+enum Message {
+    Packet(Packet),
+    // the machine at this address halted, or failed with the error
+    Stopped(usize, Option<anyhow::Error>),
+}
+
+fn run_threaded(program: &[i64], size: usize) -> Result<NatReport> {
+    let shutdown = AtomicBool::new(false);
+    // packets sent by machines that the router has not taken yet
+    let unrouted = AtomicUsize::new(0);
+    // per machine: values routed to it that it has not read yet, and empty reads in a row
+    let queued: Vec<AtomicUsize> = (0..size).map(|_| AtomicUsize::new(0)).collect();
+    let idle_reads: Vec<AtomicUsize> = (0..size).map(|_| AtomicUsize::new(0)).collect();
+
+    let (message_tx, message_rx) = mpsc::channel::<Message>();
+
+    thread::scope(|scope| {
+        let mut senders = vec![];
+        let mut handles = vec![];
+
+        for (address, (queued, idle)) in queued.iter().zip(&idle_reads).enumerate() {
+            let (tx, rx) = mpsc::channel::<i64>();
+            senders.push(tx);
+
+            let message_tx = message_tx.clone();
+            let (shutdown, unrouted) = (&shutdown, &unrouted);
+
+            handles.push(scope.spawn(move || -> Result<()> {
+                let mut partial = vec![];
+                let packet_tx = message_tx.clone();
+
+                let input = input_fn(|| {
+                    if shutdown.load(Ordering::SeqCst) {
+                        return None;
+                    }
+
+                    match rx.try_recv() {
+                        Ok(value) => {
+                            idle.store(0, Ordering::SeqCst);
+                            queued.fetch_sub(1, Ordering::SeqCst);
+                            Some(value)
+                        }
+                        Err(TryRecvError::Empty) => {
+                            idle.fetch_add(1, Ordering::SeqCst);
+                            thread::yield_now();
+                            Some(-1)
+                        }
+                        Err(TryRecvError::Disconnected) => None,
+                    }
+                });
+                let output = output_fn(|value| {
+                    idle.store(0, Ordering::SeqCst);
+                    partial.push(value);
+
+                    if partial.len() == 3 {
+                        let packet = Packet {
+                            address: partial[0],
+                            x: partial[1],
+                            y: partial[2],
+                        };
+                        partial.clear();
+
+                        unrouted.fetch_add(1, Ordering::SeqCst);
+                        packet_tx
+                            .send(Message::Packet(packet))
+                            .map_err(|_| anyhow!("router stopped, packet {:?} dropped", packet))?;
+                    }
+                    Ok(())
+                });
+
+                let mut machine = ProgramState::new(program, input, output);
+                machine.feed(address as i64);
+
+                let result = loop {
+                    machine.set_step_limit(Some(machine.steps() + THREAD_SLICE));
+                    match machine.run() {
+                        Err(IntcodeError::StepLimitExceeded { .. })
+                            if !shutdown.load(Ordering::SeqCst) => {}
+                        result => break result,
+                    }
+                };
+                let error = match result {
+                    Err(_) if shutdown.load(Ordering::SeqCst) => return Ok(()),
+                    Ok(_) => None,
+                    Err(e) => Some(anyhow::Error::from(e)),
+                };
+
+                // hand the error to the router, or keep it if the router is already gone
+                match message_tx.send(Message::Stopped(address, error)) {
+                    Ok(()) => Ok(()),
+                    Err(mpsc::SendError(Message::Stopped(_, Some(error)))) => Err(error),
+                    Err(_) => Ok(()),
+                }
+            }));
+        }
+        drop(message_tx);
+
+        let result = route_threaded(&message_rx, &senders, &unrouted, &queued, &idle_reads);
+
+        shutdown.store(true, Ordering::SeqCst);
+        drop(senders);
+
+        for handle in handles {
+            match handle.join() {
+                Ok(machine) => machine?,
+                Err(_) => return Err(anyhow!("machine thread panicked")),
+            }
+        }
+
+        result
+    })
+}
+
+fn route_threaded(
+    messages: &mpsc::Receiver<Message>,
+    senders: &[mpsc::Sender<i64>],
+    unrouted: &AtomicUsize,
+    queued: &[AtomicUsize],
+    idle_reads: &[AtomicUsize],
+) -> Result<NatReport> {
+    let mut nat = Nat::default();
+    // halted machines count as idle, like in round-robin scheduling
+    let mut stopped = vec![false; senders.len()];
+
+    let deliver = |packet: Packet| -> Result<()> {
+        let address = match target(&packet, senders.len())? {
+            Some(address) => address,
+            None => return Err(anyhow!("NAT cannot deliver to itself")),
+        };
+
+        queued[address].fetch_add(2, Ordering::SeqCst);
+        for value in [packet.x, packet.y] {
+            // a machine that stopped never reads its packets, its queue does not count for idleness
+            let _ = senders[address].send(value);
+        }
+        Ok(())
+    };
+
+    loop {
+        match messages.recv_timeout(ROUTER_POLL) {
+            Ok(Message::Packet(packet)) => {
+                match target(&packet, senders.len())? {
+                    Some(_) => deliver(packet)?,
+                    None => nat.receive(packet),
+                }
+                unrouted.fetch_sub(1, Ordering::SeqCst);
+            }
+            Ok(Message::Stopped(_, Some(error))) => return Err(error),
+            Ok(Message::Stopped(address, None)) => stopped[address] = true,
+            Err(RecvTimeoutError::Timeout) => {
+                let idle = unrouted.load(Ordering::SeqCst) == 0
+                    && (0..senders.len()).all(|address| {
+                        stopped[address]
+                            || (queued[address].load(Ordering::SeqCst) == 0
+                                && idle_reads[address].load(Ordering::SeqCst) >= IDLE_READS)
+                    });
+
+                if idle {
+                    match nat.wake()? {
+                        Ok(packet) => deliver(packet)?,
+                        Err(report) => return Ok(report),
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(anyhow!("all machines stopped before the NAT finished"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{run_network, NatReport, Packet, Scheduling, NAT_ADDRESS};
+
+    // Node of a four machine ring: machine 0 boots by sending (0, 5) to machine 1, every machine
+    // forwards a received (x, y) as (x + 1, y) to the next address, machine 3 sends to the NAT.
+    fn ring_node() -> Vec<i64> {
+        let mut program = vec![
+            3, 100, // in [A]
+            1008, 100, 0, 101, // eq [A], 0 -> [101]
+            1006, 101, 15, // jf [101], LOOP
+            104, 1, 104, 0, 104, 5, // out 1, 0, 5
+            3, 102, // LOOP: in [x]
+            1008, 102, -1, 103, // eq [x], -1 -> [103]
+            1005, 103, 15, // jt [103], LOOP
+            3, 104, // in [y]
+            1008, 100, 3, 103, // eq [A], 3 -> [103]
+            1005, 103, 40, // jt [103], LAST
+            1001, 100, 1, 105, // [dest] = [A] + 1
+            1105, 1, 44, // jmp SEND
+            1101, 255, 0, 105, // LAST: [dest] = 255
+            4, 105, // SEND: out [dest]
+            1001, 102, 1, 102, // [x] += 1
+            4, 102, 4, 104, // out [x], [y]
+            1105, 1, 15, // jmp LOOP
+        ];
+        program.resize(106, 0);
+        program
+    }
+
+    fn expected() -> NatReport {
+        NatReport {
+            first_packet: Packet {
+                address: NAT_ADDRESS,
+                x: 3,
+                y: 5,
+            },
+            repeated_y: 5,
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        assert_eq!(
+            run_network(&ring_node(), 4, Scheduling::RoundRobin).unwrap(),
+            expected()
+        );
+    }
+
+    #[test]
+    fn threaded() {
+        assert_eq!(
+            run_network(&ring_node(), 4, Scheduling::Threaded).unwrap(),
+            expected()
+        );
+    }
+
+    #[test]
+    fn unknown_address() {
+        // machine sends a packet to address 7 right away
+        let program = vec![3, 20, 104, 7, 104, 0, 104, 0, 3, 20, 1105, 1, 8];
+
+        assert!(run_network(&program, 2, Scheduling::RoundRobin).is_err());
+        assert!(run_network(&program, 2, Scheduling::Threaded).is_err());
+
+        // machine 0 sends to address 7, machine 1 spins without ever reading
+        let program = vec![
+            3, 20, 1005, 20, 14, 104, 7, 104, 0, 104, 0, 1105, 1, 11, 1105, 1, 14,
+        ];
+        for scheduling in [Scheduling::RoundRobin, Scheduling::Threaded] {
+            let error = run_network(&program, 2, scheduling).unwrap_err();
+            assert!(error.to_string().contains("unknown address"));
+        }
+    }
+
+    // machine 0 runs the instruction at 5 after booting, the others poll for packets forever
+    fn stopping_node(instruction: i64) -> Vec<i64> {
+        vec![3, 100, 1005, 100, 6, instruction, 3, 101, 1105, 1, 6]
+    }
+
+    #[test]
+    fn machine_halts() {
+        for scheduling in [Scheduling::RoundRobin, Scheduling::Threaded] {
+            let error = run_network(&stopping_node(99), 2, scheduling).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "network is idle and NAT has no packet to send"
+            );
+        }
+    }
+
+    #[test]
+    fn machine_faults() {
+        for scheduling in [Scheduling::RoundRobin, Scheduling::Threaded] {
+            let error = run_network(&stopping_node(77), 3, scheduling).unwrap_err();
+            assert_eq!(error.to_string(), "invalid opcode at 5 (instruction 77)");
+        }
+    }
+}
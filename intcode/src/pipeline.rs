@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+
+use crate::computer::{ProgramState, Status};
+
+// How the outputs of the machines are wired to the inputs of the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    // machine i feeds machine i + 1, the last machine produces the final signal
+    Series,
+    // like Series, but the last machine also feeds the first one
+    FeedbackLoop,
+}
+
+// Runs one copy of `program` per phase setting, wired according to `topology`.
+// Every machine gets its phase setting as the first input, the first machine then gets 0.
+// Machines take turns until all of them halt; returns the last value the last machine produced.
+pub fn run_pipeline(program: &[i64], phases: &[i64], topology: Topology) -> Result<i64> {
+    if phases.is_empty() {
+        return Err(anyhow!("pipeline needs at least one machine"));
+    }
+
+    let mut machines: Vec<ProgramState> = phases
+        .iter()
+        .map(|&phase| {
+            let mut machine = ProgramState::new(program, VecDeque::new(), vec![]);
+            machine.set_pause_on_output(true);
+            machine.feed(phase);
+            machine
+        })
+        .collect();
+
+    let count = machines.len();
+    let mut halted = vec![false; count];
+    let mut signal = None;
+
+    machines[0].feed(0);
+
+    while halted.iter().any(|h| !h) {
+        let mut progressed = false;
+
+        for i in 0..count {
+            if halted[i] {
+                continue;
+            }
+
+            loop {
+                match machines[i].run()? {
+                    Status::Output(value) => {
+                        progressed = true;
+
+                        if i + 1 < count {
+                            machines[i + 1].feed(value);
+                        } else {
+                            signal = Some(value);
+                            if topology == Topology::FeedbackLoop {
+                                machines[0].feed(value);
+                            }
+                        }
+                    }
+                    Status::NeedsInput => break,
+                    Status::Halted => {
+                        progressed = true;
+                        halted[i] = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !progressed {
+            return Err(anyhow!("all running machines are waiting for input"));
+        }
+    }
+
+    signal.ok_or_else(|| anyhow!("last machine halted without producing a signal"))
+}
+
+// Tries every ordering of `phases` and returns the highest signal with the ordering producing it.
+pub fn max_thruster_signal(
+    program: &[i64],
+    phases: &[i64],
+    topology: Topology,
+) -> Result<(i64, Vec<i64>)> {
+    let mut best: Option<(i64, Vec<i64>)> = None;
+
+    for order in permutations(phases) {
+        let signal = run_pipeline(program, &order, topology)?;
+
+        match best {
+            Some((max, _)) if max >= signal => {}
+            _ => best = Some((signal, order)),
+        }
+    }
+
+    best.ok_or_else(|| anyhow!("no phase settings given"))
+}
+
+// all orderings of the values, Heap's algorithm
+fn permutations(values: &[i64]) -> Vec<Vec<i64>> {
+    let mut values = values.to_vec();
+    let mut result = vec![values.clone()];
+    let mut counters = vec![0; values.len()];
+
+    let mut i = 1;
+    while i < values.len() {
+        if counters[i] < i {
+            if i % 2 == 0 {
+                values.swap(0, i);
+            } else {
+                values.swap(counters[i], i);
+            }
+            result.push(values.clone());
+
+            counters[i] += 1;
+            i = 1;
+        } else {
+            counters[i] = 0;
+            i += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{max_thruster_signal, permutations, run_pipeline, Topology};
+
+    #[test]
+    fn all_permutations() {
+        let mut perms = permutations(&[0, 1, 2]);
+        perms.sort();
+
+        assert_eq!(
+            perms,
+            vec![
+                vec![0, 1, 2],
+                vec![0, 2, 1],
+                vec![1, 0, 2],
+                vec![1, 2, 0],
+                vec![2, 0, 1],
+                vec![2, 1, 0],
+            ]
+        );
+        assert_eq!(permutations(&[0, 1, 2, 3, 4]).len(), 120);
+    }
+
+    #[test]
+    fn series() {
+        let program = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+
+        assert_eq!(
+            run_pipeline(&program, &[4, 3, 2, 1, 0], Topology::Series).unwrap(),
+            43210
+        );
+        assert_eq!(
+            max_thruster_signal(&program, &[0, 1, 2, 3, 4], Topology::Series).unwrap(),
+            (43210, vec![4, 3, 2, 1, 0])
+        );
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        assert_eq!(
+            run_pipeline(&program, &[9, 8, 7, 6, 5], Topology::FeedbackLoop).unwrap(),
+            139629729
+        );
+        assert_eq!(
+            max_thruster_signal(&program, &[5, 6, 7, 8, 9], Topology::FeedbackLoop).unwrap(),
+            (139629729, vec![9, 8, 7, 6, 5])
+        );
+    }
+
+    #[test]
+    fn deadlock() {
+        // every machine reads three values before producing output
+        let program = vec![3, 9, 3, 9, 3, 9, 4, 9, 99, 0];
+
+        assert!(run_pipeline(&program, &[1, 2], Topology::Series).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::cfg::Cfg;
+use crate::computer::InstructionType;
+use crate::disassembler::{decode, disassemble, mnemonic, Line};
+
+// Execution counts collected by a ProgramState with profiling enabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    // instruction address -> times executed
+    pub executions: BTreeMap<usize, u64>,
+    // mnemonic -> times executed
+    pub opcodes: BTreeMap<&'static str, u64>,
+    // cell -> times an instruction read it through a position or relative parameter
+    pub reads: BTreeMap<usize, u64>,
+    // cell -> times an instruction stored to it
+    pub writes: BTreeMap<usize, u64>,
+}
+
+impl Profile {
+    pub(crate) fn record_execution(
+        &mut self,
+        ip: usize,
+        opcode: &InstructionType,
+        reads: &[usize],
+    ) {
+        *self.executions.entry(ip).or_default() += 1;
+        *self.opcodes.entry(mnemonic(opcode)).or_default() += 1;
+
+        for &addr in reads {
+            *self.reads.entry(addr).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_write(&mut self, addr: usize) {
+        *self.writes.entry(addr).or_default() += 1;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.executions.values().sum()
+    }
+
+    // the `n` most executed instruction addresses, most executed first
+    pub fn hot(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self.executions.iter().map(|(&a, &c)| (a, c)).collect();
+        hot.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+        hot.truncate(n);
+        hot
+    }
+
+    // instructions reachable in the control-flow graph of `code` that never ran
+    pub fn unexecuted(&self, code: &[i64]) -> Vec<Line> {
+        Cfg::build(code)
+            .blocks
+            .into_values()
+            .flat_map(|block| block.lines)
+            .filter(|line| match line {
+                Line::Instruction { address, .. } => !self.executions.contains_key(address),
+                Line::Data { .. } => false,
+            })
+            .collect()
+    }
+
+    // Summary of the run against the program image `code`:
+    //      steps: 10
+    //      hot addresses:
+    //             3     0: OUT [13]
+    //             3     2: ADD [13], #-1, [13]
+    //      opcodes:
+    //           ADD      3
+    //      ...
+    //      unexecuted code:
+    pub fn report(&self, code: &[i64], top: usize) -> String {
+        let mut report = format!("steps: {}\nhot addresses:\n", self.steps());
+
+        for (addr, count) in self.hot(top) {
+            let body = decode(code.get(addr..).unwrap_or_default(), addr)
+                .map(|line| line.body())
+                .unwrap_or_default();
+            writeln!(report, "{:>8} {:>5}: {}", count, addr, body).unwrap();
+        }
+
+        report.push_str("opcodes:\n");
+        let mut opcodes: Vec<(&str, u64)> = self.opcodes.iter().map(|(&m, &c)| (m, c)).collect();
+        opcodes.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+        for (mnemonic, count) in opcodes {
+            writeln!(report, "{:>8} {:>6}", mnemonic, count).unwrap();
+        }
+
+        report.push_str("unexecuted code:\n");
+        for line in self.unexecuted(code) {
+            writeln!(report, "{}", line).unwrap();
+        }
+
+        report
+    }
+
+    // The disassembly of `code` with the executions of each instruction and the reads and writes
+    // of the cells on each line, blank where there were none:
+    //         execs  reads writes
+    //             3                   2: ADD [13], #-1, [13]
+    //                    9      3    13: DATA 3
+    pub fn annotated(&self, code: &[i64]) -> String {
+        let mut listing = format!("{:>8} {:>6} {:>6}\n", "execs", "reads", "writes");
+
+        for line in disassemble(code) {
+            let (address, len) = match &line {
+                Line::Instruction {
+                    address, operands, ..
+                } => (*address, 1 + operands.len()),
+                Line::Data { address, values } => (*address, values.len()),
+            };
+            let executions = match line {
+                Line::Instruction { .. } => self.executions.get(&address).copied().unwrap_or(0),
+                Line::Data { .. } => 0,
+            };
+            let count = |counts: &BTreeMap<usize, u64>| -> u64 {
+                counts.range(address..address + len).map(|(_, &c)| c).sum()
+            };
+
+            writeln!(
+                listing,
+                "{:>8} {:>6} {:>6} {}",
+                blank_zero(executions),
+                blank_zero(count(&self.reads)),
+                blank_zero(count(&self.writes)),
+                line
+            )
+            .unwrap();
+        }
+
+        listing
+    }
+}
+
+fn blank_zero(count: u64) -> String {
+    match count {
+        0 => String::new(),
+        count => count.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::computer::ProgramState;
+    use crate::profile::Profile;
+
+    // counts [13] down from 3, outputs it on every pass and halts
+    fn countdown() -> Vec<i64> {
+        vec![4, 13, 1001, 13, -1, 13, 1005, 13, 0, 99, 104, 7, 99, 3]
+    }
+
+    fn profiled(program: &[i64]) -> Profile {
+        let mut prog = ProgramState::new(program, VecDeque::new(), vec![]);
+        prog.set_profiling(true);
+        prog.run_to_halt().unwrap();
+
+        prog.profile().cloned().unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let profile = profiled(&countdown());
+
+        assert_eq!(profile.steps(), 10);
+        assert_eq!(profile.hot(2), vec![(0, 3), (2, 3)]);
+        assert_eq!(profile.opcodes["JT"], 3);
+        assert_eq!(profile.reads[&13], 9);
+        assert_eq!(profile.writes[&13], 3);
+        assert_eq!(profile.unexecuted(&countdown()).len(), 0);
+    }
+
+    #[test]
+    fn report() {
+        let profile = profiled(&[1002, 4, 3, 4, 33]);
+
+        assert_eq!(
+            profile.report(&[1002, 4, 3, 4, 99], 5),
+            "steps: 2\nhot addresses:\n       1     0: MUL [4], #3, [4]\n       1     4: HLT\n\
+             opcodes:\n     HLT      1\n     MUL      1\nunexecuted code:\n"
+        );
+        assert_eq!(
+            profile.annotated(&[1002, 4, 3, 4, 99]),
+            "   execs  reads writes\n       1                   0: MUL [4], #3, [4]\n       \
+             1      1      1     4: HLT\n"
+        );
+    }
+
+    #[test]
+    fn unexecuted_branch() {
+        let program = [3, 9, 1005, 9, 7, 104, 0, 99, 0, 0];
+        let mut prog = ProgramState::new(&program, VecDeque::from(vec![1]), vec![]);
+        prog.set_profiling(true);
+        prog.run_to_halt().unwrap();
+
+        let unexecuted: Vec<String> = prog
+            .profile()
+            .unwrap()
+            .unexecuted(&program)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(unexecuted, vec!["    5: OUT #0"]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use anyhow::{anyhow, Result};
+use rayon::prelude::*;
+
+use crate::computer::{Engine, ProgramState, Snapshot, Status};
+
+// candidates stop after this many instructions unless `step_limit` says otherwise,
+// patched programs easily end up in an endless loop
+pub const DEFAULT_STEP_LIMIT: u64 = 1_000_000;
+
+// An address to patch and the values to try there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub address: usize,
+    pub values: Range<i64>,
+}
+
+// A candidate that ran to HALT, as seen by the predicate.
+pub struct Outcome<'r> {
+    // patched values in the order the patches were added
+    pub values: &'r [i64],
+    pub output: &'r [i64],
+    state: &'r ProgramState<'static>,
+}
+
+impl Outcome<'_> {
+    // memory after the program halted
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.state.peek(addr)
+    }
+}
+
+// Tries every combination of patched values on a program, in parallel.
+// Like nested loops over the patches, the last patch added varies fastest; candidates that fault,
+// wait for more input or exceed the step limit never match.
+#[derive(Debug, Clone)]
+pub struct Search {
+    start: Snapshot,
+    patches: Vec<Patch>,
+    input: Vec<i64>,
+    step_limit: u64,
+}
+
+impl Search {
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            start: ProgramState::new(program, VecDeque::new(), vec![]).snapshot(),
+            patches: vec![],
+            input: vec![],
+            step_limit: DEFAULT_STEP_LIMIT,
+        }
+    }
+
+    pub fn patch(mut self, address: usize, values: Range<i64>) -> Self {
+        self.patches.push(Patch { address, values });
+        self
+    }
+
+    // input every candidate reads
+    pub fn input(mut self, input: Vec<i64>) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn step_limit(mut self, limit: u64) -> Self {
+        self.step_limit = limit;
+        self
+    }
+
+    // number of combinations to try, an error when it does not fit in a usize
+    pub fn candidates(&self) -> Result<usize> {
+        self.patches.iter().try_fold(1_usize, |count, patch| {
+            size(&patch.values)
+                .and_then(|size| count.checked_mul(size))
+                .ok_or_else(|| anyhow!("too many candidates to enumerate"))
+        })
+    }
+
+    // the first matching parameter set in loop order
+    pub fn first<P>(&self, predicate: P) -> Result<Option<Vec<i64>>>
+    where
+        P: Fn(&Outcome) -> bool + Sync,
+    {
+        Ok((0..self.candidates()?)
+            .into_par_iter()
+            .map(|n| self.values(n))
+            .find_first(|values| self.matches(values, &predicate)))
+    }
+
+    // every matching parameter set in loop order
+    pub fn all<P>(&self, predicate: P) -> Result<Vec<Vec<i64>>>
+    where
+        P: Fn(&Outcome) -> bool + Sync,
+    {
+        Ok((0..self.candidates()?)
+            .into_par_iter()
+            .map(|n| self.values(n))
+            .filter(|values| self.matches(values, &predicate))
+            .collect())
+    }
+
+    // the n-th combination, counting like an odometer whose last wheel is the last patch; only
+    // called below `candidates`, so every size fits
+    fn values(&self, mut n: usize) -> Vec<i64> {
+        let mut values = vec![0; self.patches.len()];
+
+        for (value, patch) in values.iter_mut().zip(&self.patches).rev() {
+            let size = size(&patch.values).expect("counted by candidates");
+            // the offset can exceed i64::MAX, the sum still lies inside the range
+            *value = patch.values.start.wrapping_add_unsigned((n % size) as u64);
+            n /= size;
+        }
+
+        values
+    }
+
+    fn matches<P>(&self, values: &[i64], predicate: &P) -> bool
+    where
+        P: Fn(&Outcome) -> bool,
+    {
+        let mut state =
+            ProgramState::from_snapshot(&self.start, VecDeque::from(self.input.clone()), vec![]);
+        state.set_engine(Engine::Fast);
+        state.set_pause_on_output(true);
+        state.set_step_limit(Some(self.step_limit));
+
+        for (patch, &value) in self.patches.iter().zip(values) {
+            state.poke(patch.address, value);
+        }
+
+        let mut output = vec![];
+        loop {
+            match state.run() {
+                Ok(Status::Output(value)) => output.push(value),
+                Ok(Status::Halted) => break,
+                Ok(Status::NeedsInput) | Err(_) => return false,
+            }
+        }
+
+        predicate(&Outcome {
+            values,
+            output: &output,
+            state: &state,
+        })
+    }
+}
+
+// number of values in `range`, None when that does not fit in a usize
+fn size(range: &Range<i64>) -> Option<usize> {
+    match range.end > range.start {
+        true => usize::try_from(range.end.abs_diff(range.start)).ok(),
+        false => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::Search;
+
+    // outputs the product of the values at 1 and 2
+    fn multiplier() -> Vec<i64> {
+        vec![1102, 0, 0, 7, 4, 7, 99, 0]
+    }
+
+    #[test]
+    fn all_and_first() {
+        let search = Search::new(&multiplier()).patch(1, 0..10).patch(2, 0..10);
+        assert_eq!(search.candidates().unwrap(), 100);
+
+        let found = search.all(|outcome| outcome.output == [12]).unwrap();
+        assert_eq!(found, vec![vec![2, 6], vec![3, 4], vec![4, 3], vec![6, 2]]);
+
+        let first = search.first(|outcome| outcome.peek(7) == 12).unwrap();
+        assert_eq!(first, Some(vec![2, 6]));
+
+        assert_eq!(
+            search.first(|outcome| outcome.output == [100]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn faulting_candidates_never_match() {
+        // JT [1], #n jumps to the patched n: 0 loops forever, 1 and 5 run into invalid opcodes,
+        // 4 reads input and 2 multiplies two cells before reaching HLT
+        let program = vec![1005, 1, 4, 99, 3, 0, 99];
+        let search = Search::new(&program).patch(2, 0..7).step_limit(1000);
+
+        assert_eq!(
+            search.all(|_| true).unwrap(),
+            vec![vec![2], vec![3], vec![6]]
+        );
+        assert_eq!(
+            search.clone().input(vec![5]).all(|_| true).unwrap(),
+            vec![vec![2], vec![3], vec![4], vec![6]]
+        );
+    }
+
+    #[test]
+    fn empty_range() {
+        let search = Search::new(&multiplier()).patch(1, 0..10).patch(2, 5..5);
+
+        assert_eq!(search.candidates().unwrap(), 0);
+        assert!(search.all(|_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn huge_ranges() {
+        let search = Search::new(&multiplier()).patch(1, i64::MIN..i64::MIN + 3);
+        assert_eq!(search.candidates().unwrap(), 3);
+        assert_eq!(
+            search.all(|_| true).unwrap(),
+            vec![vec![i64::MIN], vec![i64::MIN + 1], vec![i64::MIN + 2]]
+        );
+
+        let search = Search::new(&multiplier()).patch(1, i64::MIN..0);
+        assert_eq!(search.candidates().unwrap(), 1 << 63);
+
+        let search = Search::new(&multiplier())
+            .patch(1, 0..1 << 40)
+            .patch(2, 0..1 << 40);
+        assert!(search.candidates().is_err());
+        assert!(search.first(|_| true).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::computer::parse_instruction;
+use crate::trace::{TraceEvent, Tracer};
+
+// A write to a cell that was executed as part of an instruction, before or after the write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modification {
+    // step and address of the instruction that wrote the cell
+    pub step: u64,
+    pub ip: usize,
+    // disassembled writing instruction, e.g. "ADD [225], [6], [6]"
+    pub instruction: String,
+    pub address: usize,
+    pub value: i64,
+    // first step that executed the cell before the write, the writing instruction itself included
+    pub executed_before: Option<u64>,
+    // first step that executed the cell after the write
+    pub executed_after: Option<u64>,
+}
+
+//      1     2: ADD [13], [6], [6]  [6]=1101  executed at 2
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} {:>5}: {}  [{}]={}",
+            self.step, self.ip, self.instruction, self.address, self.value
+        )?;
+        if let Some(step) = self.executed_before {
+            write!(f, "  executed before at {}", step)?;
+        }
+        if let Some(step) = self.executed_after {
+            write!(f, "  executed at {}", step)?;
+        }
+        Ok(())
+    }
+}
+
+// Finds self-modifying code: installed with `set_tracer`, it remembers every cell executed as an
+// opcode or parameter and every write, and reports the writes that land on executed cells.
+#[derive(Debug, Default)]
+pub struct SelfModTracer {
+    // cell -> first step that executed it
+    executed: HashMap<usize, u64>,
+    // every write so far, most of them to data
+    writes: Vec<Modification>,
+    // cell -> writes to it not yet followed by executing it
+    pending: HashMap<usize, Vec<usize>>,
+}
+
+impl SelfModTracer {
+    // writes to executed cells in the order they happened
+    pub fn report(&self) -> Vec<&Modification> {
+        self.writes
+            .iter()
+            .filter(|write| write.executed_before.is_some() || write.executed_after.is_some())
+            .collect()
+    }
+
+    // `image` with the writes that were executed later applied: the code as it ran, for static
+    // analysis of programs that patch themselves before running the patched instructions
+    pub fn patched(&self, image: &[i64]) -> Vec<i64> {
+        let mut memory = image.to_vec();
+        for write in self.report() {
+            if write.executed_after.is_some() && write.address < memory.len() {
+                memory[write.address] = write.value;
+            }
+        }
+        memory
+    }
+
+    // executed cells no write touched, safe to pre-decode
+    pub fn stable_code(&self) -> Vec<usize> {
+        let modified: Vec<usize> = self.report().iter().map(|write| write.address).collect();
+        let mut cells: Vec<usize> = self
+            .executed
+            .keys()
+            .copied()
+            .filter(|addr| !modified.contains(addr))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+}
+
+impl Tracer for SelfModTracer {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()> {
+        // the opcode and the parameters, which are read before the instruction writes anything
+        let count = parse_instruction(event.ip, event.code)?.opcode.arg_count();
+        for addr in event.ip..=event.ip + count {
+            self.executed.entry(addr).or_insert(event.step);
+
+            for write in self.pending.remove(&addr).unwrap_or_default() {
+                self.writes[write].executed_after = Some(event.step);
+            }
+        }
+
+        for &(address, value) in &event.writes {
+            self.pending
+                .entry(address)
+                .or_default()
+                .push(self.writes.len());
+            self.writes.push(Modification {
+                step: event.step,
+                ip: event.ip,
+                instruction: event.instruction.clone(),
+                address,
+                value,
+                executed_before: self.executed.get(&address).copied(),
+                executed_after: None,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::computer::ProgramState;
+    use crate::selfmod::{Modification, SelfModTracer};
+
+    fn analyse(program: &[i64], input: Vec<i64>) -> (SelfModTracer, Vec<i64>) {
+        let mut tracer = SelfModTracer::default();
+        let mut output = vec![];
+        let mut prog = ProgramState::new(program, VecDeque::from(input), &mut output);
+
+        prog.set_tracer(&mut tracer);
+        prog.run_to_halt().unwrap();
+        drop(prog);
+
+        (tracer, output)
+    }
+
+    #[test]
+    fn patched_ahead() {
+        // like the day5 prologue: adds the input to the opcode at 6, turning 1100 into 1101
+        let program = [3, 13, 1, 13, 6, 6, 1100, 1, 41, 13, 4, 13, 99, 0];
+        let (tracer, output) = analyse(&program, vec![1]);
+
+        assert_eq!(output, vec![42]);
+        assert_eq!(
+            tracer.report(),
+            vec![&Modification {
+                step: 1,
+                ip: 2,
+                instruction: "ADD [13], [6], [6]".to_string(),
+                address: 6,
+                value: 1101,
+                executed_before: None,
+                executed_after: Some(2),
+            }]
+        );
+        assert_eq!(
+            tracer.report()[0].to_string(),
+            "     1     2: ADD [13], [6], [6]  [6]=1101  executed at 2"
+        );
+        assert_eq!(
+            tracer.stable_code(),
+            vec![0, 1, 2, 3, 4, 5, 7, 8, 9, 10, 11, 12]
+        );
+    }
+
+    #[test]
+    fn rewritten_behind() {
+        // decrements its own opcode after running it, then jumps on [1] and halts
+        let program = [1001, 0, -1, 0, 1005, 1, 0, 99];
+        let (tracer, _) = analyse(&program, vec![]);
+        let report = tracer.report();
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].address, 0);
+        assert_eq!(report[0].value, 1000);
+        assert_eq!(report[0].executed_before, Some(0));
+        assert_eq!(report[0].executed_after, None);
+        assert_eq!(tracer.patched(&program), program);
+    }
+
+    #[test]
+    fn data_writes_ignored() {
+        let (tracer, output) = analyse(&[1002, 7, 3, 7, 4, 7, 99, 33], vec![]);
+
+        assert_eq!(output, vec![99]);
+        assert!(tracer.report().is_empty());
+    }
+}
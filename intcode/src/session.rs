@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::computer::Snapshot;
+
+// first bytes of a binary session, a JSON session starts with `{`
+const MAGIC: &[u8] = b"ICSESS1\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    // bincode behind MAGIC
+    Binary,
+}
+
+impl Format {
+    // `.json` files are JSON, everything else is binary
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Binary,
+        }
+    }
+}
+
+// A paused machine together with the output it produced so far, enough to pick an interactive
+// program up again in a later process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub machine: Snapshot,
+    pub output: Vec<i64>,
+}
+
+impl Session {
+    pub fn to_bytes(&self, format: Format) -> Result<Vec<u8>> {
+        match format {
+            Format::Json => Ok(serde_json::to_vec(self)?),
+            Format::Binary => {
+                let mut bytes = MAGIC.to_vec();
+                bincode::serialize_into(&mut bytes, self)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    // reads either format, telling them apart by the leading bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.strip_prefix(MAGIC) {
+            Some(body) => Ok(bincode::deserialize(body)?),
+            None if bytes.trim_ascii_start().starts_with(b"{") => {
+                Ok(serde_json::from_slice(bytes)?)
+            }
+            None => Err(anyhow!("not an Intcode session")),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes(Format::from_path(path))?;
+
+        fs::write(path, bytes).with_context(|| format!("writing session {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|| format!("reading session {}", path.display()))?;
+
+        Self::from_bytes(&bytes).with_context(|| format!("loading session {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::computer::{ProgramState, Status};
+    use crate::session::{Format, Session};
+
+    // reads two numbers, writes their sum far past the program and outputs it
+    fn paused() -> Session {
+        let memory = vec![3, 13, 3, 14, 1, 13, 14, 100000, 4, 100000, 99, 0, 0, 0, 0];
+        let mut output = vec![];
+        let mut prog = ProgramState::new(&memory, VecDeque::from(vec![2]), &mut output);
+
+        assert_eq!(prog.run().unwrap(), Status::NeedsInput);
+        prog.feed(40);
+        let machine = prog.snapshot();
+        drop(prog);
+
+        Session { machine, output }
+    }
+
+    fn resume(session: Session) -> Vec<i64> {
+        let mut output = session.output;
+        let mut prog = ProgramState::from_snapshot(&session.machine, VecDeque::new(), &mut output);
+
+        assert_eq!(prog.run().unwrap(), Status::Halted);
+        assert_eq!(prog.peek(100000), 42);
+        assert_eq!(prog.input_pos(), 2);
+        drop(prog);
+
+        output
+    }
+
+    #[test]
+    fn round_trip() {
+        for format in [Format::Json, Format::Binary] {
+            let bytes = paused().to_bytes(format).unwrap();
+            let session = Session::from_bytes(&bytes).unwrap();
+
+            assert_eq!(session, paused());
+            assert_eq!(resume(session), vec![42]);
+        }
+    }
+
+    #[test]
+    fn json_layout() {
+        let json = String::from_utf8(paused().to_bytes(Format::Json).unwrap()).unwrap();
+
+        assert!(json.starts_with("{\"machine\":{\"memory\":{\"image\":[3,13,3,14,"));
+        assert!(json.contains("\"ip\":2,"));
+        assert!(json.contains("\"pending_input\":[40],"));
+        assert!(json.ends_with("\"output\":[]}"));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Session::from_bytes(b"3,0,4,0,99").is_err());
+        assert!(Session::from_bytes(b"ICSESS1\n\x01").is_err());
+        assert!(Session::from_bytes(b"{\"machine\": 1}").is_err());
+    }
+}
@@ -0,0 +1,538 @@
+// Symbolic execution: memory cells hold polynomials over named variables instead of numbers, so
+// running a program tells how its results depend on the cells bound to variables.
+//
+// Control flow has to stay concrete: opcodes, jump conditions, comparisons and addresses written
+// to must evaluate to constants. Reading through an address that depends on a variable yields an
+// unknown value, which is fine as long as nothing concrete or final is computed from it; day2's
+// first instruction does exactly that before its result is overwritten.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use anyhow::{anyhow, Result};
+
+use crate::computer::{parse_instruction, InstructionArgumentMode, InstructionType};
+
+// symbolic runs give up after this many instructions
+pub const STEP_LIMIT: u64 = 1_000_000;
+
+// A polynomial with integer coefficients over named variables.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Expr {
+    // sorted names of the variables multiplied in a term to its coefficient; the empty list is
+    // the constant term, zero coefficients are never stored
+    terms: BTreeMap<Vec<String>, i64>,
+}
+
+impl Expr {
+    pub fn constant(value: i64) -> Self {
+        Self::term(vec![], value)
+    }
+
+    pub fn var(name: &str) -> Self {
+        Self::term(vec![name.to_string()], 1)
+    }
+
+    fn term(monomial: Vec<String>, coefficient: i64) -> Self {
+        let mut terms = BTreeMap::new();
+        if coefficient != 0 {
+            terms.insert(monomial, coefficient);
+        }
+        Self { terms }
+    }
+
+    // the value if no variable is involved
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((monomial, &c)) if monomial.is_empty() && self.terms.len() == 1 => Some(c),
+            Some(_) => None,
+        }
+    }
+
+    pub fn variables(&self) -> BTreeSet<&str> {
+        self.terms
+            .keys()
+            .flatten()
+            .map(|name| name.as_str())
+            .collect()
+    }
+
+    // coefficient of the term multiplying exactly `names`, e.g. &["noun"] or &[] for the constant
+    pub fn coefficient(&self, names: &[&str]) -> i64 {
+        let mut monomial: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        monomial.sort();
+        self.terms.get(&monomial).copied().unwrap_or(0)
+    }
+
+    // the expression with `name` replaced by `value`, None if a coefficient overflows
+    pub fn substitute(&self, name: &str, value: i64) -> Option<Expr> {
+        let mut result = Expr::default();
+
+        for (monomial, &c) in &self.terms {
+            let mut coefficient = c;
+            let mut rest = vec![];
+            for var in monomial {
+                if var == name {
+                    coefficient = coefficient.checked_mul(value)?;
+                } else {
+                    rest.push(var.clone());
+                }
+            }
+            result = result.checked_add(&Expr::term(rest, coefficient))?;
+        }
+
+        Some(result)
+    }
+
+    // the sum, None if a coefficient overflows
+    pub fn checked_add(&self, other: &Expr) -> Option<Expr> {
+        let mut terms = self.terms.clone();
+
+        for (monomial, &c) in &other.terms {
+            let sum = terms.get(monomial).copied().unwrap_or(0).checked_add(c)?;
+            if sum == 0 {
+                terms.remove(monomial);
+            } else {
+                terms.insert(monomial.clone(), sum);
+            }
+        }
+
+        Some(Expr { terms })
+    }
+
+    // the product, None if a coefficient overflows
+    pub fn checked_mul(&self, other: &Expr) -> Option<Expr> {
+        let mut result = Expr::default();
+
+        for (a, &ca) in &self.terms {
+            for (b, &cb) in &other.terms {
+                let mut monomial: Vec<String> = a.iter().chain(b).cloned().collect();
+                monomial.sort();
+                result = result.checked_add(&Expr::term(monomial, ca.checked_mul(cb)?))?;
+            }
+        }
+
+        Some(result)
+    }
+
+    // every assignment to the variables in `domains` that makes the expression equal `target`,
+    // in the order of nested loops over the domains with the last one innermost. The last
+    // variable is solved for, so the expression has to be linear in it, the others are
+    // enumerated.
+    pub fn solve(&self, target: i64, domains: &[(&str, Range<i64>)]) -> Result<Vec<Vec<i64>>> {
+        let ((solved, range), enumerated) = domains
+            .split_last()
+            .ok_or_else(|| anyhow!("nothing to solve for"))?;
+        if let Some(unbound) = self
+            .variables()
+            .into_iter()
+            .find(|var| domains.iter().all(|(name, _)| name != var))
+        {
+            return Err(anyhow!("no domain given for {}", unbound));
+        }
+
+        let sizes: Vec<usize> = enumerated
+            .iter()
+            .map(|(_, range)| range.end.saturating_sub(range.start).max(0) as usize)
+            .collect();
+        let combinations = sizes
+            .iter()
+            .try_fold(1_usize, |n, &size| n.checked_mul(size))
+            .ok_or_else(|| anyhow!("too many assignments to enumerate"))?;
+        let mut solutions = vec![];
+
+        for n in 0..combinations {
+            // odometer over the enumerated variables, the last one turning fastest
+            let mut values = vec![0; enumerated.len()];
+            let mut rest = n;
+            for i in (0..enumerated.len()).rev() {
+                values[i] = enumerated[i].1.start + (rest % sizes[i]) as i64;
+                rest /= sizes[i];
+            }
+
+            let mut expr = self.clone();
+            for ((name, _), &value) in enumerated.iter().zip(&values) {
+                expr = expr
+                    .substitute(name, value)
+                    .ok_or_else(|| anyhow!("{} overflows with {} = {}", self, name, value))?;
+            }
+
+            let a = expr.coefficient(&[solved]);
+            let c = expr.coefficient(&[]);
+            if expr.terms.len() > (a != 0) as usize + (c != 0) as usize {
+                return Err(anyhow!("{} is not linear in {}", self, solved));
+            }
+
+            // a * x + c == target, in 128 bits so target - c cannot overflow
+            let rest = i128::from(target) - i128::from(c);
+            let xs: Vec<i64> = match i128::from(a) {
+                0 if rest == 0 => range.clone().collect(),
+                0 => vec![],
+                a if rest % a == 0 => i64::try_from(rest / a)
+                    .ok()
+                    .filter(|x| range.contains(x))
+                    .into_iter()
+                    .collect(),
+                _ => vec![],
+            };
+            for x in xs {
+                let mut solution = values.clone();
+                solution.push(x);
+                solutions.push(solution);
+            }
+        }
+
+        Ok(solutions)
+    }
+}
+
+// highest degree terms first, e.g. "360000 * noun + verb + 337061"
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+
+        let mut terms: Vec<(&Vec<String>, i64)> = self.terms.iter().map(|(m, &c)| (m, c)).collect();
+        terms.sort_by_key(|(monomial, _)| Reverse(monomial.len()));
+
+        for (i, (monomial, c)) in terms.into_iter().enumerate() {
+            match (i, c < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            let mut factors: Vec<String> = monomial.clone();
+            if c.unsigned_abs() != 1 || monomial.is_empty() {
+                factors.insert(0, c.unsigned_abs().to_string());
+            }
+            write!(f, "{}", factors.join(" * "))?;
+        }
+
+        Ok(())
+    }
+}
+
+// None marks a value read through an address that depends on a variable
+type Cell = Option<Expr>;
+
+// Runs an Intcode program over symbolic memory until it halts.
+#[derive(Debug, Clone)]
+pub struct SymbolicMachine {
+    memory: HashMap<usize, Cell>,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<Expr>,
+    output: Vec<Cell>,
+}
+
+impl SymbolicMachine {
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            memory: program
+                .iter()
+                .enumerate()
+                .map(|(addr, &value)| (addr, Some(Expr::constant(value))))
+                .collect(),
+            ip: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: vec![],
+        }
+    }
+
+    // replaces the cell at `addr` with the variable `name`
+    pub fn bind(&mut self, addr: usize, name: &str) {
+        self.memory.insert(addr, Some(Expr::var(name)));
+    }
+
+    // queues a value for READ, numbers go in as `Expr::constant`
+    pub fn feed(&mut self, value: Expr) {
+        self.input.push_back(value);
+    }
+
+    pub fn peek(&self, addr: usize) -> Result<Expr> {
+        known(self.cell(addr), || format!("[{}]", addr))
+    }
+
+    pub fn output(&self) -> Result<Vec<Expr>> {
+        self.output
+            .iter()
+            .enumerate()
+            .map(|(i, value)| known(value.clone(), || format!("output {}", i)))
+            .collect()
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        for _ in 0..STEP_LIMIT {
+            let code = self.concrete(self.cell(self.ip), "opcode")?;
+            let instruction = parse_instruction(self.ip, code)?;
+            let argument = |n: usize| self.argument(&instruction.arg_modes[n], n);
+
+            match instruction.opcode {
+                InstructionType::ADD | InstructionType::MULTIPLY => {
+                    let result = match (argument(0)?, argument(1)?) {
+                        (Some(a), Some(b)) if instruction.opcode == InstructionType::ADD => {
+                            Some(a.checked_add(&b).ok_or_else(|| self.overflow())?)
+                        }
+                        (Some(a), Some(b)) => {
+                            Some(a.checked_mul(&b).ok_or_else(|| self.overflow())?)
+                        }
+                        _ => None,
+                    };
+                    self.store(&instruction.arg_modes[2], 2, result)?;
+                }
+                InstructionType::LESS_THAN | InstructionType::EQUALS => {
+                    let a = self.concrete(argument(0)?, "compared value")?;
+                    let b = self.concrete(argument(1)?, "compared value")?;
+                    let result = match instruction.opcode {
+                        InstructionType::LESS_THAN => a < b,
+                        _ => a == b,
+                    };
+                    self.store(
+                        &instruction.arg_modes[2],
+                        2,
+                        Some(Expr::constant(result as i64)),
+                    )?;
+                }
+                InstructionType::JUMP_IF_TRUE | InstructionType::JUMP_IF_FALSE => {
+                    let a = self.concrete(argument(0)?, "jump condition")?;
+                    let target = self.concrete(argument(1)?, "jump target")?;
+
+                    if (a != 0) == (instruction.opcode == InstructionType::JUMP_IF_TRUE) {
+                        self.ip = self.address(target)?;
+                        continue;
+                    }
+                }
+                InstructionType::READ => {
+                    let value = self
+                        .input
+                        .pop_front()
+                        .ok_or_else(|| anyhow!("{}: input exhausted", self.ip))?;
+                    self.store(&instruction.arg_modes[0], 0, Some(value))?;
+                }
+                InstructionType::WRITE => {
+                    let value = argument(0)?;
+                    self.output.push(value);
+                }
+                InstructionType::ADJUST_RELATIVE_BASE => {
+                    let offset = self.concrete(argument(0)?, "relative base offset")?;
+                    self.relative_base = self
+                        .relative_base
+                        .checked_add(offset)
+                        .ok_or_else(|| self.overflow())?;
+                }
+                InstructionType::HALT => return Ok(()),
+            }
+
+            self.ip += 1 + instruction.opcode.arg_count();
+        }
+
+        Err(anyhow!("no HALT within {} steps", STEP_LIMIT))
+    }
+
+    fn cell(&self, addr: usize) -> Cell {
+        match self.memory.get(&addr) {
+            Some(cell) => cell.clone(),
+            None => Some(Expr::constant(0)),
+        }
+    }
+
+    fn concrete(&self, value: Cell, what: &str) -> Result<i64> {
+        match value.as_ref().and_then(|expr| expr.as_constant()) {
+            Some(value) => Ok(value),
+            None => Err(anyhow!(
+                "{}: {} depends on {}",
+                self.ip,
+                what,
+                describe(&value)
+            )),
+        }
+    }
+
+    fn overflow(&self) -> anyhow::Error {
+        anyhow!("{}: arithmetic overflow", self.ip)
+    }
+
+    fn address(&self, value: i64) -> Result<usize> {
+        usize::try_from(value).map_err(|_| anyhow!("{}: negative address {}", self.ip, value))
+    }
+
+    // address of the n-th parameter in position or relative mode, None when it is symbolic
+    fn target(&self, mode: &InstructionArgumentMode, n: usize) -> Result<Option<usize>> {
+        let param = self.cell(self.ip + 1 + n);
+        let base = match mode {
+            InstructionArgumentMode::POSITION => 0,
+            InstructionArgumentMode::RELATIVE => self.relative_base,
+            InstructionArgumentMode::IMMEDIATE => {
+                unreachable!("immediate parameters have no address")
+            }
+        };
+
+        match param.and_then(|expr| expr.as_constant()) {
+            Some(offset) => {
+                let address = base.checked_add(offset).ok_or_else(|| self.overflow())?;
+                Ok(Some(self.address(address)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn argument(&self, mode: &InstructionArgumentMode, n: usize) -> Result<Cell> {
+        match mode {
+            InstructionArgumentMode::IMMEDIATE => Ok(self.cell(self.ip + 1 + n)),
+            _ => Ok(self.target(mode, n)?.and_then(|addr| self.cell(addr))),
+        }
+    }
+
+    fn store(&mut self, mode: &InstructionArgumentMode, n: usize, value: Cell) -> Result<()> {
+        if *mode == InstructionArgumentMode::IMMEDIATE {
+            return Err(anyhow!(
+                "{}: parameter {} is written to in immediate mode",
+                self.ip,
+                n + 1
+            ));
+        }
+
+        match self.target(mode, n)? {
+            Some(addr) => {
+                self.memory.insert(addr, value);
+                Ok(())
+            }
+            None => Err(anyhow!(
+                "{}: address written to depends on {}",
+                self.ip,
+                describe(&self.cell(self.ip + 1 + n))
+            )),
+        }
+    }
+}
+
+fn known(value: Cell, what: impl Fn() -> String) -> Result<Expr> {
+    value.ok_or_else(|| {
+        anyhow!(
+            "{} was read through an address depending on a variable",
+            what()
+        )
+    })
+}
+
+fn describe(value: &Cell) -> String {
+    match value {
+        Some(expr) => expr.to_string(),
+        None => "a value read through a symbolic address".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbolic::{Expr, SymbolicMachine};
+
+    #[test]
+    fn arithmetic_and_display() {
+        let x = Expr::var("x");
+        let y = Expr::var("y");
+        let add = |a: &Expr, b: &Expr| a.checked_add(b).unwrap();
+        let mul = |a: &Expr, b: &Expr| a.checked_mul(b).unwrap();
+        let e = mul(
+            &add(&mul(&x, &Expr::constant(3)), &y),
+            &add(&x, &Expr::constant(-2)),
+        );
+
+        assert_eq!(e.to_string(), "3 * x * x + x * y - 6 * x - 2 * y");
+        assert_eq!(e.coefficient(&["y", "x"]), 1);
+        assert_eq!(e.substitute("x", 2).unwrap().as_constant(), Some(0));
+        assert_eq!(add(&x, &Expr::constant(-1)).to_string(), "x - 1");
+        assert_eq!(add(&x, &mul(&x, &Expr::constant(-1))).to_string(), "0");
+        assert_eq!(
+            mul(&x, &Expr::constant(i64::MIN)).to_string(),
+            "-9223372036854775808 * x"
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let x = Expr::var("x");
+        let big = x.checked_mul(&Expr::constant(i64::MAX)).unwrap();
+
+        assert_eq!(big.checked_add(&x), None);
+        assert_eq!(big.checked_mul(&Expr::constant(2)), None);
+        assert_eq!(big.substitute("x", 2), None);
+        assert!(big.solve(1, &[("y", 0..2), ("x", 0..10)]).is_ok());
+
+        let error = SymbolicMachine::new(&[1102, i64::MAX, 2, 0, 99])
+            .run()
+            .unwrap_err();
+        assert_eq!(error.to_string(), "0: arithmetic overflow");
+        assert!(SymbolicMachine::new(&[109, i64::MAX, 109, 1, 99])
+            .run()
+            .is_err());
+    }
+
+    #[test]
+    fn day2_style_program() {
+        // [3] = [noun] + [verb] is dead, [0] = ([1] + [2]) * 3 * [1] + 4
+        let program = vec![
+            1, 0, 0, 3, 1, 1, 2, 3, 1002, 3, 3, 3, 2, 3, 1, 0, 1001, 0, 4, 0, 99,
+        ];
+        let mut machine = SymbolicMachine::new(&program);
+        machine.bind(1, "noun");
+        machine.bind(2, "verb");
+        machine.run().unwrap();
+
+        let result = machine.peek(0).unwrap();
+        assert_eq!(result.to_string(), "3 * noun * noun + 3 * noun * verb + 4");
+        // the dead store read through symbolic addresses
+        assert!(machine.peek(3).is_ok());
+        // noun * (noun + verb) == 6
+        assert_eq!(
+            result
+                .solve(22, &[("noun", 0..10), ("verb", 0..10)])
+                .unwrap(),
+            vec![vec![1, 5], vec![2, 1]]
+        );
+        assert!(result
+            .solve(22, &[("verb", 0..10), ("noun", 0..10)])
+            .is_err());
+    }
+
+    #[test]
+    fn solve_linear() {
+        let e = Expr::var("noun")
+            .checked_mul(&Expr::constant(100))
+            .and_then(|e| e.checked_add(&Expr::var("verb")))
+            .unwrap();
+
+        assert_eq!(
+            e.solve(1234, &[("noun", 0..100), ("verb", 0..100)])
+                .unwrap(),
+            vec![vec![12, 34]]
+        );
+        assert_eq!(
+            e.solve(250, &[("noun", 0..100), ("verb", 0..60)]).unwrap(),
+            vec![vec![2, 50]]
+        );
+        assert!(e.solve(1, &[("noun", 0..100)]).is_err());
+        assert_eq!(
+            Expr::var("x")
+                .solve(3, &[("y", 0..2), ("x", 0..10)])
+                .unwrap(),
+            vec![vec![0, 3], vec![1, 3]]
+        );
+    }
+
+    #[test]
+    fn symbolic_control_flow() {
+        // JT #x, #4
+        let mut machine = SymbolicMachine::new(&[1105, 0, 4, 99, 99]);
+        machine.bind(1, "x");
+
+        let err = machine.run().unwrap_err();
+        assert_eq!(err.to_string(), "0: jump condition depends on x");
+    }
+}
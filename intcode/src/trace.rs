@@ -0,0 +1,151 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+// One executed instruction as seen by a tracer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceEvent {
+    // number of instructions executed before this one
+    pub step: u64,
+    pub ip: usize,
+    #[serde(rename = "rb")]
+    pub relative_base: i64,
+    // raw opcode cell, e.g. 1002
+    pub code: i64,
+    // disassembled form, e.g. "MUL [4], #3, [4]"
+    pub instruction: String,
+    // parameter values after resolving their modes; the target address for parameters written to
+    pub operands: Vec<i64>,
+    // memory cells the instruction stored to, as (address, value)
+    pub writes: Vec<(usize, i64)>,
+}
+
+// Receives every instruction a ProgramState executes once installed with `set_tracer`.
+pub trait Tracer {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()>;
+}
+
+// keeps the events in memory
+impl Tracer for Vec<TraceEvent> {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+impl<T: Tracer + ?Sized> Tracer for &mut T {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()> {
+        (**self).trace(event)
+    }
+}
+
+// One JSON object per line:
+// {"step":0,"ip":0,"rb":0,"code":1002,"instruction":"MUL [4], #3, [4]","operands":[33,3,4],"writes":[[4,99]]}
+pub struct JsonLinesTracer<W: Write>(pub W);
+
+impl<W: Write> Tracer for JsonLinesTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()> {
+        serde_json::to_writer(&mut self.0, event)?;
+        writeln!(self.0)?;
+        Ok(())
+    }
+}
+
+// One line per instruction:
+//      0     0: MUL [4], #3, [4]  (33 3 4)  [4]=99
+pub struct TextTracer<W: Write>(pub W);
+
+impl<W: Write> Tracer for TextTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) -> Result<()> {
+        let operands: Vec<String> = event.operands.iter().map(|v| v.to_string()).collect();
+        let writes: Vec<String> = event
+            .writes
+            .iter()
+            .map(|(addr, value)| format!("[{}]={}", addr, value))
+            .collect();
+
+        write!(
+            self.0,
+            "{:>6} {:>5}: {}  ({})",
+            event.step,
+            event.ip,
+            event.instruction,
+            operands.join(" ")
+        )?;
+        if !writes.is_empty() {
+            write!(self.0, "  {}", writes.join(" "))?;
+        }
+        writeln!(self.0)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::computer::ProgramState;
+    use crate::trace::{JsonLinesTracer, TextTracer, TraceEvent};
+
+    fn traced<T: crate::trace::Tracer>(tracer: T) {
+        let memory = vec![1002, 4, 3, 4, 33];
+        let mut prog = ProgramState::new(&memory, VecDeque::new(), vec![]);
+
+        prog.set_tracer(tracer);
+        prog.run().unwrap();
+    }
+
+    #[test]
+    fn record_events() {
+        let mut events: Vec<TraceEvent> = vec![];
+        traced(&mut events);
+
+        assert_eq!(
+            events,
+            vec![
+                TraceEvent {
+                    step: 0,
+                    ip: 0,
+                    relative_base: 0,
+                    code: 1002,
+                    instruction: "MUL [4], #3, [4]".to_string(),
+                    operands: vec![33, 3, 4],
+                    writes: vec![(4, 99)],
+                },
+                TraceEvent {
+                    step: 1,
+                    ip: 4,
+                    relative_base: 0,
+                    code: 99,
+                    instruction: "HLT".to_string(),
+                    operands: vec![],
+                    writes: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut out = vec![];
+        traced(JsonLinesTracer(&mut out));
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"step\":0,\"ip\":0,\"rb\":0,\"code\":1002,\"instruction\":\"MUL [4], #3, [4]\",\"operands\":[33,3,4],\"writes\":[[4,99]]}\n\
+             {\"step\":1,\"ip\":4,\"rb\":0,\"code\":99,\"instruction\":\"HLT\",\"operands\":[],\"writes\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn text() {
+        let mut out = vec![];
+        traced(TextTracer(&mut out));
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "     0     0: MUL [4], #3, [4]  (33 3 4)  [4]=99\n     1     4: HLT  ()\n"
+        );
+    }
+}